@@ -0,0 +1,91 @@
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// 展開済みのソーティングネットワークを生成する最大の要素数
+const MAX_NETWORK_LEN: usize = 64;
+
+/// 固定長配列のためのソーティングネットワークを、比較器を並べただけのコードとして生成する
+/// 生成したコードはsrc/network.rsで読み込む
+fn main() {
+    let mut code = String::new();
+    let lengths = std::iter::once(0).chain((0..).map(|bits| 1 << bits));
+    for n in lengths.take_while(|&n| n <= MAX_NETWORK_LEN) {
+        write_impl(&mut code, n, &sort_schedule(n), &merge_schedule(n));
+    }
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("networks.rs");
+    fs::write(path, code).unwrap();
+    println!("cargo:rerun-if-changed=build.rs");
+}
+
+/// バイトニックソートの比較器の並び（比較する添字の組と、昇順かどうか）を返す
+fn sort_schedule(n: usize) -> Vec<(usize, usize, bool)> {
+    let mut schedule = Vec::new();
+    let mut k = 2;
+    while k <= n {
+        let mut j = k / 2;
+        while j > 0 {
+            for i in 0..n {
+                let partner = i ^ j;
+                if partner > i {
+                    schedule.push((i, partner, i & k == 0));
+                }
+            }
+            j /= 2;
+        }
+        k *= 2;
+    }
+    schedule
+}
+
+/// バイトニック列を昇順にソートする比較器の並びを返す
+fn merge_schedule(n: usize) -> Vec<(usize, usize, bool)> {
+    let mut schedule = Vec::new();
+    let mut j = n / 2;
+    while j > 0 {
+        for i in 0..n {
+            let partner = i ^ j;
+            if partner > i {
+                schedule.push((i, partner, true));
+            }
+        }
+        j /= 2;
+    }
+    schedule
+}
+
+fn write_impl(
+    code: &mut String,
+    n: usize,
+    sort: &[(usize, usize, bool)],
+    merge: &[(usize, usize, bool)],
+) {
+    writeln!(code, "impl<T> SortingNetwork<T> for [T; {}] {{", n).unwrap();
+    write_method(code, "sort_network", sort);
+    write_method(code, "merge_network", merge);
+    writeln!(code, "}}").unwrap();
+}
+
+fn write_method(code: &mut String, name: &str, schedule: &[(usize, usize, bool)]) {
+    writeln!(code, "    #[inline]").unwrap();
+    writeln!(
+        code,
+        "    fn {}<F: Fn(&T, &T) -> Ordering>(&mut self, comparator: &F) {{",
+        name
+    )
+    .unwrap();
+    if schedule.is_empty() {
+        writeln!(code, "        let _ = comparator;").unwrap();
+    }
+    for &(i, j, forward) in schedule {
+        writeln!(
+            code,
+            "        compare_and_swap(self, comparator, {}, {}, {});",
+            i, j, forward
+        )
+        .unwrap();
+    }
+    writeln!(code, "    }}").unwrap();
+}
@@ -1,7 +1,16 @@
-use num_cpus;
-
+use bitonic_sorter::batch::{sort_batch, sort_batch_interleaved};
+use bitonic_sorter::blocked::sort as blocked_sort;
+use bitonic_sorter::branchless::sort as branchless_sort;
 use bitonic_sorter::fourth::sort as par_sort;
+use bitonic_sorter::fourth::sort_by as par_sort_by;
+use bitonic_sorter::fourth::sort_hybrid as par_sort_hybrid;
+use bitonic_sorter::hybrid::BaseCase;
+use bitonic_sorter::indirect::sort_indirect_by;
+use bitonic_sorter::plan::SortPlan;
+use bitonic_sorter::prefix::sort as prefix_sort;
+use bitonic_sorter::records::{self, RecordLayout};
 use bitonic_sorter::third::sort as seq_sort;
+use bitonic_sorter::third::sort_hybrid as seq_sort_hybrid;
 use bitonic_sorter::utils::{is_sorted_ascending, new_u32_vec};
 use bitonic_sorter::SortOrder;
 
@@ -17,7 +26,7 @@ fn main() {
     } else {
         eprintln!(
             "Usage {} <number of elements in bits>",
-            env::args().nth(0).unwrap()
+            env::args().next().unwrap()
         );
         std::process::exit(1);
     }
@@ -43,6 +52,218 @@ fn run_sorts(bits: u32) {
     let par_duration = time_sort(&par_sort, len, "par_sort");
 
     println!("Speed up: {:.2}x", seq_duration / par_duration);
+
+    let branchless_duration = time_sort(&branchless_sort, len, "branchless_sort");
+
+    println!(
+        "Speed up by branchless: {:.2}x",
+        seq_duration / branchless_duration
+    );
+
+    let blocked_duration = time_sort(&blocked_sort, len, "blocked_sort");
+
+    println!(
+        "Speed up by blocking: {:.2}x",
+        seq_duration / blocked_duration
+    );
+
+    // 小さな配列をまとめてソートする場合に、要素を交互に並べた場合と比べる
+    for chunk_len in &[16, 64, 256] {
+        let chunk_len = (*chunk_len).min(len);
+        let per_chunk = |array: &mut [u32], order: &SortOrder| sort_batch(array, chunk_len, order);
+        let interleaved =
+            |array: &mut [u32], order: &SortOrder| sort_batch_interleaved(array, chunk_len, order);
+
+        let per_chunk_duration = time_batch_sort(&per_chunk, len, chunk_len, "sort_batch");
+        let interleaved_duration =
+            time_batch_sort(&interleaved, len, chunk_len, "sort_batch_interleaved");
+
+        println!(
+            "Speed up by interleaving {} elements: {:.2}x",
+            chunk_len,
+            per_chunk_duration / interleaved_duration
+        );
+    }
+
+    // 小さな部分配列の基底ケースを切り替えた場合と比べる
+    for (base_case, name) in &[
+        (BaseCase::Network, "network"),
+        (BaseCase::Insertion, "insertion"),
+    ] {
+        let seq_hybrid =
+            |array: &mut [u32], order: &SortOrder| seq_sort_hybrid(array, order, base_case);
+        let par_hybrid =
+            |array: &mut [u32], order: &SortOrder| par_sort_hybrid(array, order, base_case);
+
+        let seq_hybrid_duration = time_sort(&seq_hybrid, len, &format!("seq_sort_{}", name));
+        let par_hybrid_duration = time_sort(&par_hybrid, len, &format!("par_sort_{}", name));
+
+        println!(
+            "Speed up by {}: seq {:.2}x, par {:.2}x",
+            name,
+            seq_duration / seq_hybrid_duration,
+            par_duration / par_hybrid_duration
+        );
+    }
+
+    // 小さい配列と中くらいの配列で、実行計画を再利用した場合の効果を測る
+    for plan_bits in &[4, 8, 12, 16] {
+        time_plan_sort(*plan_bits);
+    }
+
+    // 大きな要素を直接ソートした場合と、添字をソートした場合を比べる
+    time_large_sort(bits.min(16));
+
+    // 文字列を直接ソートした場合と、先頭8バイトをキャッシュした場合を比べる
+    time_string_sort(bits.min(20));
+
+    // gensort形式の100バイトのレコードをソートする
+    time_record_sort(bits.min(20));
+}
+
+fn time_record_sort(bits: u32) {
+    let count = 1 << bits;
+    let mut data = records::generate(0, count);
+    let layout = RecordLayout::GENSORT;
+    let before = records::validate(&data, &layout).expect("Failed to validate: ");
+
+    let start = Instant::now();
+    records::sort_records(&mut data, &layout, &SortOrder::Ascending).expect("Failed to sort: ");
+    let secs = start.elapsed().as_secs_f64();
+
+    println!(
+        "{} records ({:.1} MB): record_sort {} seconds",
+        count,
+        data.len() as f64 / 1024.0 / 1024.0,
+        secs
+    );
+
+    let after = records::validate(&data, &layout).expect("Failed to validate: ");
+    assert!(after.is_sorted());
+    assert_eq!(after.checksum, before.checksum);
+}
+
+fn time_string_sort(bits: u32) {
+    let len = 1 << bits;
+    let array: Vec<String> = new_u32_vec(len)
+        .into_iter()
+        .map(|v| format!("{:x}", v))
+        .collect();
+
+    let mut direct = array.clone();
+    let start = Instant::now();
+    par_sort(&mut direct, &SortOrder::Ascending).expect("Failed to sort: ");
+    let direct_secs = start.elapsed().as_secs_f64();
+
+    let mut cached = array;
+    let start = Instant::now();
+    prefix_sort(&mut cached, &SortOrder::Ascending).expect("Failed to sort: ");
+    let cached_secs = start.elapsed().as_secs_f64();
+
+    println!(
+        "{} strings: par_sort {} seconds, prefix_sort {} seconds ({:.2}x)",
+        len,
+        direct_secs,
+        cached_secs,
+        direct_secs / cached_secs
+    );
+
+    assert!(is_sorted_ascending(&cached));
+}
+
+/// 大きな要素。キーの他に、ソート中に移動させるだけのデータを持つ
+#[derive(Clone)]
+struct Large {
+    key: u32,
+    _payload: [u64; 63],
+}
+
+fn time_large_sort(bits: u32) {
+    let len = 1 << bits;
+    let array: Vec<Large> = new_u32_vec(len)
+        .into_iter()
+        .map(|key| Large {
+            key,
+            _payload: [0; 63],
+        })
+        .collect();
+    let comparator = |a: &Large, b: &Large| a.key.cmp(&b.key);
+
+    let mut direct = array.clone();
+    let start = Instant::now();
+    par_sort_by(&mut direct, &comparator).expect("Failed to sort: ");
+    let direct_secs = start.elapsed().as_secs_f64();
+
+    let mut indirect = array;
+    let start = Instant::now();
+    sort_indirect_by(&mut indirect, &comparator).expect("Failed to sort: ");
+    let indirect_secs = start.elapsed().as_secs_f64();
+
+    println!(
+        "{} structs of {} bytes: par_sort {} seconds, indirect_sort {} seconds ({:.2}x)",
+        len,
+        std::mem::size_of::<Large>(),
+        direct_secs,
+        indirect_secs,
+        direct_secs / indirect_secs
+    );
+
+    assert!(indirect.windows(2).all(|w| w[0].key <= w[1].key));
+}
+
+fn time_plan_sort(bits: u32) {
+    let len = 1 << bits;
+    let iterations = (1 << 20) / len;
+    let arrays: Vec<Vec<u32>> = (0..iterations).map(|_| new_u32_vec(len)).collect();
+
+    let mut par_arrays = arrays.clone();
+    let start = Instant::now();
+    for array in par_arrays.iter_mut() {
+        par_sort(array, &SortOrder::Ascending).expect("Failed to sort: ");
+    }
+    let par_secs = start.elapsed().as_secs_f64();
+
+    let mut plan_arrays = arrays;
+    let start = Instant::now();
+    let plan = SortPlan::new(len, &SortOrder::Ascending).expect("Failed to plan: ");
+    for array in plan_arrays.iter_mut() {
+        plan.execute(array).expect("Failed to sort: ");
+    }
+    let plan_secs = start.elapsed().as_secs_f64();
+
+    println!(
+        "{} x {} integers: par_sort {} seconds, plan_sort {} seconds ({:.2}x)",
+        iterations,
+        len,
+        par_secs,
+        plan_secs,
+        par_secs / plan_secs
+    );
+
+    assert!(plan_arrays.iter().all(|array| is_sorted_ascending(array)));
+}
+
+fn time_batch_sort<F>(sorter: &F, len: usize, chunk_len: usize, name: &str) -> f64
+where
+    F: Fn(&mut [u32], &SortOrder) -> Result<(), String>,
+{
+    let mut array = new_u32_vec(len);
+
+    let start = Instant::now();
+    sorter(&mut array, &SortOrder::Ascending).expect("Failed to sort: ");
+    let secs = start.elapsed().as_secs_f64();
+
+    println!(
+        "{}: sorted {} arrays of {} integers in {} seconds",
+        name,
+        len / chunk_len,
+        chunk_len,
+        secs
+    );
+
+    assert!(array.chunks(chunk_len).all(is_sorted_ascending));
+
+    secs
 }
 
 fn time_sort<F>(sorter: &F, len: usize, name: &str) -> f64
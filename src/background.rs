@@ -0,0 +1,197 @@
+use crate::fourth;
+use crate::SortOrder;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// ソートの結果。ソートしたVecか、要素数が2^nではない場合の元のVecとエラー
+type Output<T> = Result<Vec<T>, (Vec<T>, String)>;
+
+/// バックグラウンドのソートと、結果を待つ側で共有する状態
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    finished: Condvar,
+}
+
+struct State<T> {
+    /// ソートの結果。ソート中にパニックした場合はErr
+    output: Option<thread::Result<Output<T>>>,
+    /// Futureとして待っている場合、結果が出たときに起こすWaker
+    waker: Option<Waker>,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// バックグラウンドで実行中のソートのハンドル
+/// joinで同期的に待つか、Futureとしてawaitすると、ソートしたVecを受け取れる
+pub struct SortHandle<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// rayonのスレッドプールでfourth::sortを実行し、すぐにハンドルを返す
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。また、要素はOrdを実装しなければならない。
+/// - order : ソート順
+/// # 戻り値
+/// - ソートの結果を受け取るためのハンドル。ソートできなかった場合も、結果のErrで配列を返す
+pub fn spawn_sort<T>(mut array: Vec<T>, order: &SortOrder) -> SortHandle<T>
+where
+    T: Ord + Send + 'static,
+{
+    let order = *order;
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            output: None,
+            waker: None,
+        }),
+        finished: Condvar::new(),
+    });
+
+    let sender = Arc::clone(&shared);
+    rayon::spawn(move || {
+        // パニックした場合も、待っている側に知らせてから再びパニックさせる
+        let output = panic::catch_unwind(AssertUnwindSafe(|| {
+            match fourth::sort(&mut array, &order) {
+                Ok(()) => Ok(array),
+                Err(e) => Err((array, e)),
+            }
+        }));
+
+        let waker = {
+            let mut state = sender.lock();
+            state.output = Some(output);
+            state.waker.take()
+        };
+        sender.finished.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    });
+
+    SortHandle { shared }
+}
+
+impl<T> SortHandle<T> {
+    /// ソートが終わっているかどうかを返す
+    pub fn is_finished(&self) -> bool {
+        self.shared.lock().output.is_some()
+    }
+
+    /// ソートが終わるまで現在のスレッドをブロックし、結果を返す
+    /// # 戻り値
+    /// - ソートしたVec。要素数が2^nではない場合、ソートしていない元のVecとエラーメッセージをErrで返す
+    /// # パニック
+    /// - ソート中に比較がパニックした場合、同じパニックを再び起こす
+    pub fn join(self) -> Output<T> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(output) = state.output.take() {
+                return output.unwrap_or_else(|payload| panic::resume_unwind(payload));
+            }
+            state = self
+                .shared
+                .finished
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+}
+
+impl<T> Future for SortHandle<T> {
+    type Output = Output<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.lock();
+        match state.output.take() {
+            Some(output) => {
+                Poll::Ready(output.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+            }
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::spawn_sort;
+    use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use crate::SortOrder::*;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake};
+    use std::thread::{self, Thread};
+
+    /// 待っているスレッドを起こすWaker
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// 特定のランタイムに依存せず、Futureが終わるまで現在のスレッドで待つ
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn join_sorted_vec() {
+        let handle = spawn_sort(new_u32_vec(1 << 16), &Ascending);
+
+        let x = handle.join().unwrap();
+
+        assert_eq!(x.len(), 1 << 16);
+        assert!(is_sorted_ascending(&x));
+    }
+
+    #[test]
+    fn await_sorted_vec() {
+        let handle = spawn_sort(new_u32_vec(1 << 16), &Descending);
+
+        let x = block_on(handle).unwrap();
+
+        assert!(is_sorted_descending(&x));
+    }
+
+    #[test]
+    fn is_finished_before_join() {
+        let handle = spawn_sort(vec![3u32, 1, 2, 0], &Ascending);
+        while !handle.is_finished() {
+            thread::yield_now();
+        }
+
+        assert_eq!(handle.join(), Ok(vec![0, 1, 2, 3]));
+    }
+
+    // 要素が2のべき乗個ではない場合
+    #[test]
+    fn sort_elemtns_not_power_of_two() {
+        let handle = spawn_sort(vec![45u32, 21, 11], &Ascending);
+
+        let (x, message) = block_on(handle).unwrap_err();
+        assert_eq!(x, vec![45, 21, 11]);
+        assert!(message.contains("power of two"));
+    }
+}
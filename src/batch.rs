@@ -0,0 +1,333 @@
+use crate::branchless::compare_and_swap_pair;
+use crate::network::compare_and_swap;
+use crate::SortOrder;
+use crate::SortOrder::*;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+
+/// 同時に処理する配列の数。各レーンが異なる配列の同じ位置の要素を受け持つ
+const LANES: usize = 8;
+
+/// 配列をchunk_len個ずつの配列に分け、それぞれを独立にソートする
+/// # 引数
+/// - array : 配列。ただし、要素数はchunk_lenの倍数でなければならない。また、要素はOrdを実装しなければならない。
+/// - chunk_len : 1つの配列の要素数。2^nでなければならない
+/// - order : ソート順
+/// # 戻り値
+/// - chunk_lenが2^nで、要素数がその倍数の場合Okを、それ以外の場合Errを返す
+pub fn sort_batch<T: Ord + Send>(
+    array: &mut [T],
+    chunk_len: usize,
+    order: &SortOrder,
+) -> Result<(), String> {
+    match *order {
+        Ascending => sort_batch_by(array, chunk_len, &|a, b| a.cmp(b)),
+        Descending => sort_batch_by(array, chunk_len, &|a, b| b.cmp(a)),
+    }
+}
+
+/// 配列をchunk_len個ずつの配列に分け、それぞれを独立にソートする
+/// # 引数
+/// - array : 配列。ただし、要素数はchunk_lenの倍数でなければならない。
+/// - chunk_len : 1つの配列の要素数。2^nでなければならない
+/// - comparator : 大小比較するためのクロージャ
+/// # 戻り値
+/// - chunk_lenが2^nで、要素数がその倍数の場合Okを、それ以外の場合Errを返す
+pub fn sort_batch_by<T, F>(array: &mut [T], chunk_len: usize, comparator: &F) -> Result<(), String>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    check_len(array, chunk_len)?;
+
+    // LANES個の配列をまとめて1つのタスクとし、タスク間を並列化する
+    array
+        .par_chunks_mut(chunk_len * LANES)
+        .for_each(|group| sort_lanes(group, chunk_len, comparator));
+    Ok(())
+}
+
+/// Copyな要素の配列をchunk_len個ずつの配列に分け、それぞれを独立にソートする
+/// LANES個の配列ごとに要素を交互に並べ替えた作業用の配列に写し、各レーンが連続するようにしてからソートする
+/// # 引数
+/// - array : 配列。ただし、要素数はchunk_lenの倍数でなければならない。また、要素はOrdとCopyを実装しなければならない。
+/// - chunk_len : 1つの配列の要素数。2^nでなければならない
+/// - order : ソート順
+/// # 戻り値
+/// - chunk_lenが2^nで、要素数がその倍数の場合Okを、それ以外の場合Errを返す
+pub fn sort_batch_interleaved<T: Ord + Copy + Send>(
+    array: &mut [T],
+    chunk_len: usize,
+    order: &SortOrder,
+) -> Result<(), String> {
+    match *order {
+        Ascending => sort_batch_interleaved_by(array, chunk_len, &|a, b| a.cmp(b)),
+        Descending => sort_batch_interleaved_by(array, chunk_len, &|a, b| b.cmp(a)),
+    }
+}
+
+/// Copyな要素の配列をchunk_len個ずつの配列に分け、それぞれを独立にソートする
+/// # 引数
+/// - array : 配列。ただし、要素数はchunk_lenの倍数でなければならない。また、要素はCopyを実装しなければならない。
+/// - chunk_len : 1つの配列の要素数。2^nでなければならない
+/// - comparator : 大小比較するためのクロージャ
+/// # 戻り値
+/// - chunk_lenが2^nで、要素数がその倍数の場合Okを、それ以外の場合Errを返す
+pub fn sort_batch_interleaved_by<T, F>(
+    array: &mut [T],
+    chunk_len: usize,
+    comparator: &F,
+) -> Result<(), String>
+where
+    T: Copy + Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    check_len(array, chunk_len)?;
+
+    array
+        .par_chunks_mut(chunk_len * LANES)
+        .for_each(|group| sort_interleaved(group, chunk_len, comparator));
+    Ok(())
+}
+
+fn check_len<T>(array: &[T], chunk_len: usize) -> Result<(), String> {
+    if !chunk_len.is_power_of_two() {
+        return Err(format!(
+            "The length of chunk is not a power of two. (chunk_len: {})",
+            chunk_len
+        ));
+    }
+    if !array.len().is_multiple_of(chunk_len) {
+        return Err(format!(
+            "The length of array is not a multiple of {}. (array.len(): {})",
+            chunk_len,
+            array.len()
+        ));
+    }
+    Ok(())
+}
+
+/// 連続する複数の配列を同じ比較器の順序で同時にソートする
+/// 最も内側のループはレーン（異なる配列）を回るが、要素はchunk_len個おきに離れている
+/// # 引数
+/// - group : chunk_len個ずつの配列が連続して並んだ配列
+/// - chunk_len : 1つの配列の要素数
+/// - comparator : 比較のためのクロージャ
+fn sort_lanes<T, F>(group: &mut [T], chunk_len: usize, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let lanes = group.len() / chunk_len;
+
+    // k : バイトニック列を作るブロックの大きさ、j : 比較する要素間の距離
+    let mut k = 2;
+    while k <= chunk_len {
+        let mut j = k / 2;
+        while j > 0 {
+            for i in 0..chunk_len {
+                let partner = i ^ j;
+                if partner > i {
+                    let forward = i & k == 0;
+                    for lane in 0..lanes {
+                        let base = lane * chunk_len;
+                        compare_and_swap(group, comparator, base + i, base + partner, forward);
+                    }
+                }
+            }
+            j /= 2;
+        }
+        k *= 2;
+    }
+}
+
+/// 連続する複数の配列を、要素を交互に並べた作業用の配列の上で同時にソートする
+/// 作業用の配列ではi番目の要素が各レーン分だけ連続して並ぶので、最も内側のループは連続した要素を
+/// 分岐なしで比較＆入れ替えする
+/// # 引数
+/// - group : chunk_len個ずつの配列が連続して並んだ配列
+/// - chunk_len : 1つの配列の要素数
+/// - comparator : 比較のためのクロージャ
+fn sort_interleaved<T, F>(group: &mut [T], chunk_len: usize, comparator: &F)
+where
+    T: Copy,
+    F: Fn(&T, &T) -> Ordering,
+{
+    let lanes = group.len() / chunk_len;
+
+    // buffer[i * lanes + lane] = lane番目の配列のi番目の要素
+    let mut buffer = Vec::with_capacity(group.len());
+    for i in 0..chunk_len {
+        buffer.extend((0..lanes).map(|lane| group[lane * chunk_len + i]));
+    }
+
+    let mut k = 2;
+    while k <= chunk_len {
+        let mut j = k / 2;
+        while j > 0 {
+            for i in 0..chunk_len {
+                let partner = i ^ j;
+                if partner > i {
+                    let forward = i & k == 0;
+                    let (head, tail) = buffer.split_at_mut(partner * lanes);
+                    let row = &mut head[i * lanes..(i + 1) * lanes];
+                    for (a, b) in row.iter_mut().zip(&mut tail[..lanes]) {
+                        compare_and_swap_pair(a, b, comparator, forward);
+                    }
+                }
+            }
+            j /= 2;
+        }
+        k *= 2;
+    }
+
+    for (i, row) in buffer.chunks_exact(lanes).enumerate() {
+        for (lane, &value) in row.iter().enumerate() {
+            group[lane * chunk_len + i] = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort_batch, sort_batch_by, sort_batch_interleaved, sort_batch_interleaved_by};
+    use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use crate::SortOrder::*;
+
+    // 昇順ソートのテスト
+    #[test]
+    fn sort_u32_ascending() {
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+
+        assert!(sort_batch(&mut array, 4, &Ascending).is_ok());
+
+        assert_eq!(array, vec![10, 11, 20, 30, 4, 21, 110, 330]);
+    }
+
+    //　降順ソートのテスト
+    #[test]
+    fn sort_u32_descending() {
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+
+        assert!(sort_batch(&mut array, 2, &Descending).is_ok());
+
+        assert_eq!(array, vec![30, 10, 20, 11, 330, 4, 110, 21]);
+    }
+
+    // 要素が0個
+    #[test]
+    fn sort_zero() {
+        let mut array: Vec<u32> = vec![];
+
+        assert!(sort_batch(&mut array, 8, &Ascending).is_ok());
+
+        assert_eq!(array, vec![]);
+    }
+
+    #[test]
+    fn sort_str_by_length() {
+        let mut array = vec![
+            "Rust",
+            "is",
+            "fast",
+            "and",
+            "memory-efficient",
+            "with",
+            "no",
+            "GC",
+        ];
+
+        assert!(sort_batch_by(&mut array, 4, &|a, b| a.len().cmp(&b.len()).then(a.cmp(b))).is_ok());
+
+        assert_eq!(
+            array,
+            vec![
+                "is",
+                "and",
+                "Rust",
+                "fast",
+                "GC",
+                "no",
+                "with",
+                "memory-efficient"
+            ]
+        );
+    }
+
+    // chunk_lenが2のべき乗ではない場合
+    #[test]
+    fn sort_chunk_not_power_of_two() {
+        let mut array: Vec<u32> = vec![45, 21, 11, 3, 5, 8];
+
+        assert!(sort_batch(&mut array, 3, &Ascending).is_err());
+    }
+
+    // 要素数がchunk_lenの倍数ではない場合
+    #[test]
+    fn sort_len_not_multiple_of_chunk() {
+        let mut array: Vec<u32> = vec![45, 21, 11, 3, 5, 8];
+
+        assert!(sort_batch(&mut array, 4, &Ascending).is_err());
+    }
+
+    #[test]
+    fn sort_u32_large_batch() {
+        let mut x = new_u32_vec(1 << 16);
+        let mut y = x.clone();
+
+        assert_eq!(sort_batch(&mut x, 32, &Ascending), Ok(()));
+        assert_eq!(sort_batch(&mut y, 64, &Descending), Ok(()));
+
+        assert!(x.chunks(32).all(is_sorted_ascending));
+        assert!(y.chunks(64).all(is_sorted_descending));
+    }
+
+    // 要素を交互に並べる方法でも、同じ結果になる
+    #[test]
+    fn sort_interleaved_same_as_sort_batch() {
+        // 最後のタスクの配列の数がLANESに満たない場合を含む
+        for &(len, chunk_len) in &[(1 << 16, 32), (64 * 11, 64), (8, 1)] {
+            let mut x = new_u32_vec(len);
+            let mut y = x.clone();
+
+            assert_eq!(
+                sort_batch_interleaved(&mut x, chunk_len, &Descending),
+                Ok(())
+            );
+            assert_eq!(sort_batch(&mut y, chunk_len, &Descending), Ok(()));
+
+            assert_eq!(x, y);
+        }
+    }
+
+    #[test]
+    fn sort_interleaved_by_length() {
+        let mut array = vec![
+            "Rust",
+            "is",
+            "fast",
+            "and",
+            "GC",
+            "no",
+            "with",
+            "memory-efficient",
+        ];
+
+        let comparator = |a: &&str, b: &&str| a.len().cmp(&b.len()).then(a.cmp(b));
+        assert!(sort_batch_interleaved_by(&mut array, 4, &comparator).is_ok());
+
+        assert_eq!(
+            array,
+            vec![
+                "is",
+                "and",
+                "Rust",
+                "fast",
+                "GC",
+                "no",
+                "with",
+                "memory-efficient"
+            ]
+        );
+        assert!(sort_batch_interleaved(&mut array, 3, &Ascending).is_err());
+    }
+}
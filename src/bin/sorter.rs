@@ -0,0 +1,155 @@
+use bitonic_sorter::compare::strings;
+use bitonic_sorter::fourth;
+use bitonic_sorter::merge;
+use bitonic_sorter::SortOrder;
+
+use std::cmp::Ordering;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process;
+
+const USAGE: &str = "Usage:
+  sorter sort [--order asc|desc] [--compare bytes|natural|ascii-ci|case-folded] [FILE]
+  sorter merge [--order asc|desc] [--compare bytes|natural|ascii-ci|case-folded] FILE...";
+
+/// 文字列を比較する関数
+type Comparator = fn(&String, &String) -> Ordering;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("sort") => run_sort(&args[1..]),
+        Some("merge") => run_merge(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+}
+
+/// 入力の各行をソートして標準出力に書き出す
+/// # 引数
+/// - args : sortサブコマンドの引数
+fn run_sort(args: &[String]) -> Result<(), String> {
+    let mut order = SortOrder::Ascending;
+    let mut comparator: Comparator = |a, b| a.cmp(b);
+    let mut path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--order" => order = parse_order(args.next())?,
+            "--compare" => comparator = parse_comparator(args.next())?,
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => return Err(format!("Unexpected argument {:?}.\n{}", arg, USAGE)),
+        }
+    }
+
+    let lines = match path {
+        Some(path) => {
+            let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+            read_lines(BufReader::new(file))
+        }
+        None => read_lines(io::stdin().lock()),
+    }
+    .map_err(|e| e.to_string())?;
+
+    let lines = sort_lines(lines, comparator, &order)?;
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    for line in lines {
+        writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+    }
+    out.flush().map_err(|e| e.to_string())
+}
+
+/// ソート済みの複数のファイルを併合して標準出力に書き出す
+/// # 引数
+/// - args : mergeサブコマンドの引数
+fn run_merge(args: &[String]) -> Result<(), String> {
+    let mut order = SortOrder::Ascending;
+    let mut comparator: Comparator = |a, b| a.cmp(b);
+    let mut paths = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--order" => order = parse_order(args.next())?,
+            "--compare" => comparator = parse_comparator(args.next())?,
+            _ if !arg.starts_with("--") => paths.push(arg),
+            _ => return Err(format!("Unexpected argument {:?}.\n{}", arg, USAGE)),
+        }
+    }
+    if paths.is_empty() {
+        return Err(format!("No input files.\n{}", USAGE));
+    }
+
+    let forward = order == SortOrder::Ascending;
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    merge::merge_files_by(&paths, &mut out, &|a, b| {
+        if forward {
+            comparator(a, b)
+        } else {
+            comparator(b, a)
+        }
+    })?;
+    Ok(())
+}
+
+fn read_lines<R: BufRead>(reader: R) -> io::Result<Vec<String>> {
+    reader.lines().collect()
+}
+
+fn parse_order(value: Option<&String>) -> Result<SortOrder, String> {
+    match value.map(String::as_str) {
+        Some("asc") => Ok(SortOrder::Ascending),
+        Some("desc") => Ok(SortOrder::Descending),
+        Some(value) => Err(format!(
+            "Unknown order {:?}. (expected: asc or desc)",
+            value
+        )),
+        None => Err("Missing value for --order.".to_string()),
+    }
+}
+
+fn parse_comparator(value: Option<&String>) -> Result<Comparator, String> {
+    match value.map(String::as_str) {
+        Some("bytes") => Ok(|a, b| a.cmp(b)),
+        Some("natural") => Ok(strings::natural),
+        Some("ascii-ci") => Ok(strings::ascii_case_insensitive),
+        Some("case-folded") => Ok(strings::case_folded),
+        Some(value) => Err(format!(
+            "Unknown comparator {:?}. (expected: bytes, natural, ascii-ci or case-folded)",
+            value
+        )),
+        None => Err("Missing value for --compare.".to_string()),
+    }
+}
+
+/// 行をソートする。要素数を2^nにするため、末尾にNoneを詰めてからソートする
+fn sort_lines(
+    lines: Vec<String>,
+    comparator: Comparator,
+    order: &SortOrder,
+) -> Result<Vec<String>, String> {
+    let len = lines.len().next_power_of_two();
+    let mut padded: Vec<Option<String>> = lines.into_iter().map(Some).collect();
+    padded.resize(len, None);
+
+    let forward = *order == SortOrder::Ascending;
+    fourth::sort_by(&mut padded, &|a, b| match (a, b) {
+        (Some(a), Some(b)) if forward => comparator(a, b),
+        (Some(a), Some(b)) => comparator(b, a),
+        // 詰めたNoneはソート順によらず末尾に置く
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    })?;
+
+    Ok(padded.into_iter().flatten().collect())
+}
@@ -0,0 +1,329 @@
+use crate::network::compare_and_swap;
+use crate::SortOrder;
+use crate::SortOrder::*;
+use std::cmp::Ordering;
+use std::fs;
+use std::mem;
+
+/// L2キャッシュの大きさを取得できなかった場合に使う値（バイト）
+const DEFAULT_L2_BYTES: usize = 256 * 1024;
+
+/// 大きな距離の層で、1つの要素の組と一緒に処理する連続した要素数
+const TILE_LEN: usize = 64;
+
+/// 配列をキャッシュに収まるブロック単位でソートする。配列の要素が2^nではない場合、エラーを返す
+/// ブロックの大きさはL2キャッシュの大きさから自動で決める
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。また、要素はOrdを実装しなければならない。
+/// - order : ソート順
+pub fn sort<T: Ord>(array: &mut [T], order: &SortOrder) -> Result<(), String> {
+    sort_with_block_len(array, order, detect_block_len::<T>())
+}
+
+/// 配列をキャッシュに収まるブロック単位でソートする。配列の要素が2^nではない場合、エラーを返す
+/// ブロックの大きさはL2キャッシュの大きさから自動で決める
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ
+/// # 戻り値
+/// - 要素数が2^nの場合Okを、それ以外の場合Errを返す
+pub fn sort_by<T, F>(array: &mut [T], comparator: &F) -> Result<(), String>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    sort_by_with_block_len(array, comparator, detect_block_len::<T>())
+}
+
+/// 配列を指定の要素数のブロック単位でソートする
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。また、要素はOrdを実装しなければならない。
+/// - order : ソート順
+/// - block_len : 1ブロックの要素数。2^nでなければならない
+pub fn sort_with_block_len<T: Ord>(
+    array: &mut [T],
+    order: &SortOrder,
+    block_len: usize,
+) -> Result<(), String> {
+    match *order {
+        Ascending => sort_by_with_block_len(array, &|a, b| a.cmp(b), block_len),
+        Descending => sort_by_with_block_len(array, &|a, b| b.cmp(a), block_len),
+    }
+}
+
+/// 配列を指定の要素数のブロック単位でソートする
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ
+/// - block_len : 1ブロックの要素数。2^nでなければならない
+/// # 戻り値
+/// - 要素数とblock_lenが2^nの場合Okを、それ以外の場合Errを返す
+pub fn sort_by_with_block_len<T, F>(
+    array: &mut [T],
+    comparator: &F,
+    block_len: usize,
+) -> Result<(), String>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    if !block_len.is_power_of_two() {
+        return Err(format!(
+            "The length of block is not a power of two. (block_len: {})",
+            block_len
+        ));
+    }
+    match array.len() {
+        0 => Ok(()),
+        n => {
+            if n.is_power_of_two() {
+                do_sort(array, comparator, block_len.min(n));
+                Ok(())
+            } else {
+                Err(format!(
+                    "The length of array is not a power of two. (array.len(): {})",
+                    array.len()
+                ))
+            }
+        }
+    }
+}
+
+/// L2キャッシュの半分に収まる最大のブロックの要素数（2^n）を返す
+/// L2キャッシュの大きさを取得できない環境ではDEFAULT_L2_BYTESを使う
+pub fn detect_block_len<T>() -> usize {
+    let cache_bytes = fs::read_to_string("/sys/devices/system/cpu/cpu0/cache/index2/size")
+        .ok()
+        .and_then(|size| parse_cache_size(size.trim()))
+        .unwrap_or(DEFAULT_L2_BYTES);
+
+    let len = (cache_bytes / 2 / mem::size_of::<T>().max(1)).max(2);
+    1 << (usize::BITS - 1 - len.leading_zeros())
+}
+
+/// "1024K"や"2M"のようなキャッシュの大きさをバイト数に変換する
+fn parse_cache_size(size: &str) -> Option<usize> {
+    let (digits, unit) = match size.chars().last()? {
+        'K' => (&size[..size.len() - 1], 1024),
+        'M' => (&size[..size.len() - 1], 1024 * 1024),
+        _ => (size, 1),
+    };
+    digits.parse::<usize>().ok().map(|n| n * unit)
+}
+
+/// 配列をソートする
+/// まず各ブロックをブロック内で並べ替え、その後ブロックをまたぐ大きな距離の層を
+/// 複数まとめて処理してから、残りの小さな距離の層をブロックごとに処理する
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない
+/// - comparator : 比較のためのクロージャ
+/// - block_len : 1ブロックの要素数。要素数以下の2^nでなければならない
+fn do_sort<T, F>(array: &mut [T], comparator: &F, block_len: usize)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let n = array.len();
+    let tile = TILE_LEN.min(block_len);
+    // 1回にまとめる層の数。まとめた層が触る要素数がブロックに収まるようにする
+    let layers = ((block_len / tile).trailing_zeros() as usize).max(1);
+
+    for (b, block) in array.chunks_mut(block_len).enumerate() {
+        sort_local(block, comparator, b * block_len);
+    }
+
+    let mut k = 2 * block_len;
+    while k <= n {
+        let mut j = k / 2;
+        while j >= block_len {
+            let j_lo = (j >> (layers - 1)).max(block_len);
+            merge_strided(array, comparator, k, j, j_lo, tile);
+            j = j_lo / 2;
+        }
+        for (b, block) in array.chunks_mut(block_len).enumerate() {
+            merge_local(block, comparator, b * block_len, k, block_len / 2);
+        }
+        k *= 2;
+    }
+}
+
+/// ブロックの中だけで完結する層をすべて処理する
+/// # 引数
+/// - block : ブロック
+/// - comparator : 比較のためのクロージャ
+/// - offset : ブロックの配列全体での開始位置
+fn sort_local<T, F>(block: &mut [T], comparator: &F, offset: usize)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let mut k = 2;
+    while k <= block.len() {
+        merge_local(block, comparator, offset, k, k / 2);
+        k *= 2;
+    }
+}
+
+/// ブロックの中で、距離j_startから1までの層を処理する
+/// # 引数
+/// - block : ブロック
+/// - comparator : 比較のためのクロージャ
+/// - offset : ブロックの配列全体での開始位置
+/// - k : バイトニック列を作るブロックの大きさ
+/// - j_start : 最初の層の比較する要素間の距離
+fn merge_local<T, F>(block: &mut [T], comparator: &F, offset: usize, k: usize, j_start: usize)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let mut j = j_start;
+    while j > 0 {
+        for i in 0..block.len() {
+            let partner = i ^ j;
+            if partner > i {
+                compare_and_swap(block, comparator, i, partner, (offset + i) & k == 0);
+            }
+        }
+        j /= 2;
+    }
+}
+
+/// ブロックをまたぐ距離j_hiからj_loまでの層をまとめて処理する
+/// 距離j_loの倍数だけ離れた要素の組は、これらの層の中で閉じているので、
+/// 組ごとに連続したtile個の要素を一緒にキャッシュに載せ、すべての層を処理する
+/// # 引数
+/// - array : 配列
+/// - comparator : 比較のためのクロージャ
+/// - k : バイトニック列を作るブロックの大きさ
+/// - j_hi : 最初の層の比較する要素間の距離
+/// - j_lo : 最後の層の比較する要素間の距離
+/// - tile : 一緒に処理する連続した要素数。j_lo以下でなければならない
+fn merge_strided<T, F>(
+    array: &mut [T],
+    comparator: &F,
+    k: usize,
+    j_hi: usize,
+    j_lo: usize,
+    tile: usize,
+) where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let span = 2 * j_hi;
+    let members = span / j_lo;
+
+    for window in (0..array.len()).step_by(span) {
+        for low in (0..j_lo).step_by(tile) {
+            let base = window + low;
+            let mut j = j_hi;
+            while j >= j_lo {
+                for m in 0..members {
+                    if (m * j_lo) & j != 0 {
+                        continue;
+                    }
+                    let i = base + m * j_lo;
+                    let forward = i & k == 0;
+                    for lane in 0..tile {
+                        compare_and_swap(array, comparator, i + lane, i + j + lane, forward);
+                    }
+                }
+                j /= 2;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect_block_len, parse_cache_size, sort, sort_by_with_block_len};
+    use crate::third;
+    use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use crate::SortOrder::*;
+
+    // 昇順ソートのテスト
+    #[test]
+    fn sort_u32_ascending() {
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+
+        assert!(sort(&mut array, &Ascending).is_ok());
+
+        assert_eq!(array, vec![4, 10, 11, 20, 21, 30, 110, 330]);
+    }
+
+    //　降順ソートのテスト
+    #[test]
+    fn sort_u32_descending() {
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+
+        assert!(sort(&mut array, &Descending).is_ok());
+
+        assert_eq!(array, vec![330, 110, 30, 21, 20, 11, 10, 4]);
+    }
+
+    // 要素が0個
+    #[test]
+    fn sort_zero() {
+        let mut array: Vec<u32> = vec![];
+
+        assert!(sort(&mut array, &Ascending).is_ok());
+
+        assert_eq!(array, vec![]);
+    }
+
+    // 要素が2のべき乗個ではない場合
+    #[test]
+    fn sort_elemtns_not_power_of_two() {
+        let mut array: Vec<u32> = vec![45, 21, 11];
+
+        assert!(sort(&mut array, &Descending).is_err());
+    }
+
+    // ブロックの要素数が2のべき乗ではない場合
+    #[test]
+    fn sort_block_not_power_of_two() {
+        let mut array: Vec<u32> = vec![45, 21, 11, 3];
+
+        assert!(sort_by_with_block_len(&mut array, &|a, b| a.cmp(b), 3).is_err());
+    }
+
+    // さまざまなブロックの大きさでthirdと同じ結果になる
+    #[test]
+    fn same_as_third_with_every_block_len() {
+        let x: Vec<(u32, usize)> = new_u32_vec(1 << 14)
+            .into_iter()
+            .map(|v| v % 64)
+            .zip(0..)
+            .collect();
+        let comparator = |a: &(u32, usize), b: &(u32, usize)| a.0.cmp(&b.0);
+
+        let mut expected = x.clone();
+        assert!(third::sort_by(&mut expected, &comparator).is_ok());
+
+        for bits in 1..15 {
+            let mut actual = x.clone();
+            assert!(sort_by_with_block_len(&mut actual, &comparator, 1 << bits).is_ok());
+            assert_eq!(actual, expected, "block_len: {}", 1 << bits);
+        }
+    }
+
+    #[test]
+    fn sort_u32_large() {
+        let mut x = new_u32_vec(1 << 18);
+        let mut y = x.clone();
+
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        assert_eq!(sort(&mut y, &Descending), Ok(()));
+
+        assert!(is_sorted_ascending(&x));
+        assert!(is_sorted_descending(&y));
+    }
+
+    #[test]
+    fn block_len_is_power_of_two() {
+        assert!(detect_block_len::<u8>().is_power_of_two());
+        assert!(detect_block_len::<[u8; 3000]>().is_power_of_two());
+    }
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!(parse_cache_size("1024K"), Some(1024 * 1024));
+        assert_eq!(parse_cache_size("2M"), Some(2 * 1024 * 1024));
+        assert_eq!(parse_cache_size("512"), Some(512));
+        assert_eq!(parse_cache_size(""), None);
+        assert_eq!(parse_cache_size("abc"), None);
+    }
+}
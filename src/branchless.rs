@@ -0,0 +1,197 @@
+use crate::SortOrder;
+use crate::SortOrder::*;
+use std::cmp::Ordering;
+use std::cmp::Ordering::*;
+
+/// Copyな要素の配列を分岐なしの比較＆入れ替えでソートする。配列の要素が2^nではない場合、エラーを返す
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。また、要素はOrdとCopyを実装しなければならない。
+/// - order : ソート順
+pub fn sort<T: Ord + Copy>(array: &mut [T], order: &SortOrder) -> Result<(), String> {
+    match *order {
+        Ascending => sort_by(array, &|a, b| a.cmp(b)),
+        Descending => sort_by(array, &|a, b| b.cmp(a)),
+    }
+}
+
+/// Copyな要素の配列を分岐なしの比較＆入れ替えでソートする。配列の要素が2^nではない場合、エラーを返す
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。また、要素はCopyを実装しなければならない。
+/// - comparator : 大小比較するためのクロージャ
+/// # 戻り値
+/// - 要素数が2^nの場合Okを、それ以外の場合Errを返す
+pub fn sort_by<T, F>(array: &mut [T], comparator: &F) -> Result<(), String>
+where
+    T: Copy,
+    F: Fn(&T, &T) -> Ordering,
+{
+    match array.len() {
+        0 => Ok(()),
+        n => {
+            if n.is_power_of_two() {
+                do_sort(array, comparator, true);
+                Ok(())
+            } else {
+                Err(format!(
+                    "The length of array is not a power of two. (array.len(): {})",
+                    array.len()
+                ))
+            }
+        }
+    }
+}
+
+/// 配列をソートする
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+fn do_sort<T, F>(array: &mut [T], comparator: &F, forward: bool)
+where
+    T: Copy,
+    F: Fn(&T, &T) -> Ordering,
+{
+    if array.len() <= 1 {
+        return;
+    }
+    // バイトニック列を作る
+    let mid = array.len() / 2;
+    do_sort(&mut array[..mid], comparator, true); // 前半を昇順
+    do_sort(&mut array[mid..], comparator, false); // 後半を降順でソート
+
+    sub_sort(array, comparator, forward);
+}
+
+/// バイトニック列をソートする
+/// #　引数
+/// - bitonic_array : バイトニック列
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+fn sub_sort<T, F>(bitonic_array: &mut [T], comparator: &F, forward: bool)
+where
+    T: Copy,
+    F: Fn(&T, &T) -> Ordering,
+{
+    if bitonic_array.len() <= 1 {
+        return;
+    }
+    compare_and_swap(bitonic_array, comparator, forward);
+
+    let mid = bitonic_array.len() / 2;
+    sub_sort(&mut bitonic_array[..mid], comparator, forward);
+    sub_sort(&mut bitonic_array[mid..], comparator, forward);
+}
+
+/// 各要素を要素数n / 2だけ右の要素と比較し、昇順か降順かに応じて並べ替える
+/// 入れ替えるかどうかで分岐せず、小さい方と大きい方を選んで常に両方を書き戻す
+/// # 引数
+/// - array : 並び替え対象の配列
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+fn compare_and_swap<T, F>(array: &mut [T], comparator: &F, forward: bool)
+where
+    T: Copy,
+    F: Fn(&T, &T) -> Ordering,
+{
+    let mid = array.len() / 2;
+    let (first, second) = array.split_at_mut(mid);
+
+    for (a, b) in first.iter_mut().zip(second.iter_mut()) {
+        compare_and_swap_pair(a, b, comparator, forward);
+    }
+}
+
+/// 2つの要素を比較し、昇順か降順かに応じて並べ替える
+/// 入れ替えるかどうかで分岐せず、小さい方と大きい方を選んで常に両方を書き戻す
+/// # 引数
+/// - a, b : 比較する要素
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+#[inline(always)]
+pub(crate) fn compare_and_swap_pair<T, F>(a: &mut T, b: &mut T, comparator: &F, forward: bool)
+where
+    T: Copy,
+    F: Fn(&T, &T) -> Ordering,
+{
+    let expected = if forward { Greater } else { Less };
+    let (x, y) = (*a, *b);
+    let swap = comparator(&x, &y) == expected;
+    *a = if swap { y } else { x };
+    *b = if swap { x } else { y };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort, sort_by};
+    use crate::third;
+    use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use crate::SortOrder::*;
+
+    // 昇順ソートのテスト
+    #[test]
+    fn sort_u32_ascending() {
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+
+        assert!(sort(&mut array, &Ascending).is_ok());
+
+        assert_eq!(array, vec![4, 10, 11, 20, 21, 30, 110, 330]);
+    }
+
+    //　降順ソートのテスト
+    #[test]
+    fn sort_u32_descending() {
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+
+        assert!(sort(&mut array, &Descending).is_ok());
+
+        assert_eq!(array, vec![330, 110, 30, 21, 20, 11, 10, 4]);
+    }
+
+    // 要素が0個
+    #[test]
+    fn sort_zero() {
+        let mut array: Vec<u32> = vec![];
+
+        assert!(sort(&mut array, &Ascending).is_ok());
+
+        assert_eq!(array, vec![]);
+    }
+
+    // 要素が2のべき乗個ではない場合
+    #[test]
+    fn sort_elemtns_not_power_of_two() {
+        let mut array: Vec<u32> = vec![45, 21, 11];
+
+        assert!(sort(&mut array, &Descending).is_err());
+    }
+
+    // 比較が等しい要素を入れ替えず、thirdと同じ結果になる
+    #[test]
+    fn same_as_third() {
+        let x: Vec<(u32, usize)> = new_u32_vec(1 << 12)
+            .into_iter()
+            .map(|v| v % 16)
+            .zip(0..)
+            .collect();
+        let comparator = |a: &(u32, usize), b: &(u32, usize)| a.0.cmp(&b.0);
+
+        let mut expected = x.clone();
+        let mut actual = x;
+        assert!(third::sort_by(&mut expected, &comparator).is_ok());
+        assert!(sort_by(&mut actual, &comparator).is_ok());
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn sort_u32_large() {
+        let mut x = new_u32_vec(1 << 16);
+        let mut y = x.clone();
+
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        assert_eq!(sort(&mut y, &Descending), Ok(()));
+
+        assert!(is_sorted_ascending(&x));
+        assert!(is_sorted_descending(&y));
+    }
+}
@@ -0,0 +1,170 @@
+use std::cmp::Ordering;
+use std::cmp::Ordering::*;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+/// 何回の比較に1回、比較のためのクロージャの性質を検査するか
+const SAMPLE_INTERVAL: usize = 16;
+
+/// 比較のためのクロージャが全順序になっていないことを示す。添字はソート前の配列での位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inconsistency {
+    /// compare(a, b)とcompare(b, a)が逆になっていない
+    Antisymmetry { a: usize, b: usize },
+    /// a <= b <= cなのにa <= cではない、またはa == b == cなのにa == cではない
+    Transitivity { a: usize, b: usize, c: usize },
+    /// ソート後のposition番目の要素aが、次の要素bより大きい
+    NotSorted { position: usize, a: usize, b: usize },
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Inconsistency::Antisymmetry { a, b } => write!(
+                f,
+                "The comparator is not antisymmetric. (elements: {}, {})",
+                a, b
+            ),
+            Inconsistency::Transitivity { a, b, c } => write!(
+                f,
+                "The comparator is not transitive. (elements: {}, {}, {})",
+                a, b, c
+            ),
+            Inconsistency::NotSorted { position, a, b } => write!(
+                f,
+                "The array is not sorted after sorting. (position: {}, elements: {}, {})",
+                position, a, b
+            ),
+        }
+    }
+}
+
+/// ソート中の比較を抜き取りで検査する
+/// 要素そのものではなく、ソート前の配列での添字を比較する
+pub(crate) struct Checker<'a, T, F> {
+    array: &'a [T],
+    comparator: &'a F,
+    calls: AtomicUsize,
+    /// 前回検査した比較の要素の添字。推移律の検査に使う
+    last: AtomicUsize,
+}
+
+impl<'a, T, F> Checker<'a, T, F>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    pub(crate) fn new(array: &'a [T], comparator: &'a F) -> Self {
+        Self {
+            array,
+            comparator,
+            calls: AtomicUsize::new(0),
+            last: AtomicUsize::new(0),
+        }
+    }
+
+    /// a番目とb番目の要素を比較する。抜き取った比較では、反対称律と推移律も検査する
+    /// # 引数
+    /// - a, b : 比較する要素の添字
+    /// # 戻り値
+    /// - 比較結果。検査で矛盾が見つかった場合Err
+    pub(crate) fn compare(&self, a: usize, b: usize) -> Result<Ordering, Inconsistency> {
+        let ordering = self.cmp(a, b);
+        if !self
+            .calls
+            .fetch_add(1, Relaxed)
+            .is_multiple_of(SAMPLE_INTERVAL)
+        {
+            return Ok(ordering);
+        }
+
+        if self.cmp(b, a) != ordering.reverse() {
+            return Err(Inconsistency::Antisymmetry { a, b });
+        }
+
+        let c = self.last.swap(b, Relaxed);
+        let (ab, bc, ac) = (ordering, self.cmp(b, c), self.cmp(a, c));
+        if (ab == bc && ac != ab) || (ab != Greater && bc != Greater && ac == Greater) {
+            return Err(Inconsistency::Transitivity { a, b, c });
+        }
+        Ok(ordering)
+    }
+
+    /// 添字の並びが比較のためのクロージャにしたがってソートされているか検査する
+    /// # 引数
+    /// - indices : ソート後の添字の並び
+    pub(crate) fn verify_sorted(&self, indices: &[usize]) -> Result<(), Inconsistency> {
+        match indices
+            .windows(2)
+            .position(|pair| self.cmp(pair[0], pair[1]) == Greater)
+        {
+            Some(position) => Err(Inconsistency::NotSorted {
+                position,
+                a: indices[position],
+                b: indices[position + 1],
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn cmp(&self, a: usize, b: usize) -> Ordering {
+        (self.comparator)(&self.array[a], &self.array[b])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Checker, Inconsistency};
+    use std::cmp::Ordering::*;
+
+    #[test]
+    fn detect_antisymmetry() {
+        let array = [1, 2];
+        // 常にLessを返す
+        let comparator = |_: &u32, _: &u32| Less;
+        let checker = Checker::new(&array, &comparator);
+
+        assert_eq!(
+            checker.compare(0, 1),
+            Err(Inconsistency::Antisymmetry { a: 0, b: 1 })
+        );
+    }
+
+    #[test]
+    fn detect_transitivity() {
+        // じゃんけん: 0 < 1 < 2 < 0
+        let array = [0u32, 1, 2];
+        let comparator = |a: &u32, b: &u32| {
+            if a == b {
+                Equal
+            } else if (a + 1) % 3 == *b {
+                Less
+            } else {
+                Greater
+            }
+        };
+        // 前回検査した要素は0番目
+        let checker = Checker::new(&array, &comparator);
+
+        assert_eq!(
+            checker.compare(1, 2),
+            Err(Inconsistency::Transitivity { a: 1, b: 2, c: 0 })
+        );
+    }
+
+    #[test]
+    fn verify_sorted() {
+        let array = [3u32, 1, 2];
+        let comparator = |a: &u32, b: &u32| a.cmp(b);
+        let checker = Checker::new(&array, &comparator);
+
+        assert_eq!(checker.verify_sorted(&[1, 2, 0]), Ok(()));
+        assert_eq!(
+            checker.verify_sorted(&[1, 0, 2]),
+            Err(Inconsistency::NotSorted {
+                position: 1,
+                a: 0,
+                b: 2
+            })
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use crate::SortOrder;
+use crate::SortOrder::*;
+use std::cmp::Ordering;
+use std::cmp::Ordering::*;
+
+pub mod strings;
+
+/// キーを取り出して比較するクロージャを作る
+/// # 引数
+/// - key : 要素からキーを取り出すクロージャ。キーはOrdを実装しなければならない。
+/// # 戻り値
+/// - キーの昇順に比較するクロージャ
+pub fn by_key<T, K, F>(key: F) -> impl Fn(&T, &T) -> Ordering
+where
+    K: Ord,
+    F: Fn(&T) -> K,
+{
+    move |a, b| key(a).cmp(&key(b))
+}
+
+/// Optionのキーを取り出し、Noneを先頭に置いて比較するクロージャを作る
+/// # 引数
+/// - key : 要素からOptionのキーを取り出すクロージャ
+/// # 戻り値
+/// - Noneを最小として、キーの昇順に比較するクロージャ
+pub fn nulls_first<T, K, F>(key: F) -> impl Fn(&T, &T) -> Ordering
+where
+    K: Ord,
+    F: Fn(&T) -> Option<K>,
+{
+    nulls_first_with_order(key, &Ascending)
+}
+
+/// Optionのキーを取り出し、ソート順によらずNoneを先頭に置いて比較するクロージャを作る
+/// # 引数
+/// - key : 要素からOptionのキーを取り出すクロージャ
+/// - order : Noneではないキーのソート順
+/// # 戻り値
+/// - Noneを先頭に、それ以外をorderにしたがって比較するクロージャ
+pub fn nulls_first_with_order<T, K, F>(key: F, order: &SortOrder) -> impl Fn(&T, &T) -> Ordering
+where
+    K: Ord,
+    F: Fn(&T) -> Option<K>,
+{
+    let forward = matches!(*order, Ascending);
+    move |a, b| match (key(a), key(b)) {
+        (Some(x), Some(y)) if forward => x.cmp(&y),
+        (Some(x), Some(y)) => y.cmp(&x),
+        (None, Some(_)) => Less,
+        (Some(_), None) => Greater,
+        (None, None) => Equal,
+    }
+}
+
+/// Optionのキーを取り出し、Noneを末尾に置いて比較するクロージャを作る
+/// # 引数
+/// - key : 要素からOptionのキーを取り出すクロージャ
+/// # 戻り値
+/// - Noneを最大として、キーの昇順に比較するクロージャ
+pub fn nulls_last<T, K, F>(key: F) -> impl Fn(&T, &T) -> Ordering
+where
+    K: Ord,
+    F: Fn(&T) -> Option<K>,
+{
+    nulls_last_with_order(key, &Ascending)
+}
+
+/// Optionのキーを取り出し、ソート順によらずNoneを末尾に置いて比較するクロージャを作る
+/// # 引数
+/// - key : 要素からOptionのキーを取り出すクロージャ
+/// - order : Noneではないキーのソート順
+/// # 戻り値
+/// - Noneを末尾に、それ以外をorderにしたがって比較するクロージャ
+pub fn nulls_last_with_order<T, K, F>(key: F, order: &SortOrder) -> impl Fn(&T, &T) -> Ordering
+where
+    K: Ord,
+    F: Fn(&T) -> Option<K>,
+{
+    let forward = matches!(*order, Ascending);
+    move |a, b| match (key(a), key(b)) {
+        (Some(x), Some(y)) if forward => x.cmp(&y),
+        (Some(x), Some(y)) => y.cmp(&x),
+        (None, Some(_)) => Greater,
+        (Some(_), None) => Less,
+        (None, None) => Equal,
+    }
+}
+
+/// 比較のためのクロージャを組み合わせるためのメソッド
+/// 組み合わせた結果もクロージャなので、そのままthird::sort_byやfourth::sort_byに渡せる
+/// 元のクロージャがSyncであれば、組み合わせた結果もSyncになる
+pub trait ComparatorExt<T>: Fn(&T, &T) -> Ordering + Sized {
+    /// 比較結果が等しい場合に、次のクロージャで比較する
+    /// # 引数
+    /// - next : 次に比較するためのクロージャ
+    fn then_by<G>(self, next: G) -> impl Fn(&T, &T) -> Ordering
+    where
+        G: Fn(&T, &T) -> Ordering,
+    {
+        move |a, b| self(a, b).then_with(|| next(a, b))
+    }
+
+    /// 比較結果を逆にする
+    fn reverse(self) -> impl Fn(&T, &T) -> Ordering {
+        move |a, b| self(b, a)
+    }
+
+    /// ソート順にしたがって比較する。降順の場合は比較結果を逆にする
+    /// 比較結果全体を逆にするので、nulls_firstやnulls_lastに適用するとNoneの位置も逆になる
+    /// Noneの位置を変えずに降順にする場合は、nulls_first_with_orderやnulls_last_with_orderを使う
+    /// # 引数
+    /// - order : ソート順
+    fn with_order(self, order: &SortOrder) -> impl Fn(&T, &T) -> Ordering {
+        let forward = matches!(*order, Ascending);
+        move |a, b| if forward { self(a, b) } else { self(b, a) }
+    }
+}
+
+impl<T, F> ComparatorExt<T> for F where F: Fn(&T, &T) -> Ordering {}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        by_key, nulls_first, nulls_first_with_order, nulls_last, nulls_last_with_order,
+        ComparatorExt,
+    };
+    use crate::SortOrder::*;
+    use crate::{fourth, third};
+
+    #[derive(Debug, PartialEq)]
+    struct Student {
+        first_name: String,
+        last_name: String,
+        age: Option<u8>,
+    }
+
+    impl Student {
+        fn new(first_name: &str, last_name: &str, age: Option<u8>) -> Self {
+            Self {
+                first_name: first_name.to_string(),
+                last_name: last_name.to_string(),
+                age,
+            }
+        }
+    }
+
+    #[test]
+    fn sort_students_by_name_ascending() {
+        let taro = Student::new("Taro", "Yamada", Some(16));
+        let hanako = Student::new("Hanako", "Yamada", Some(14));
+        let kyoko = Student::new("Kyoko", "Ito", Some(15));
+        let ryosuke = Student::new("Ryosuke", "Hayashi", Some(17));
+
+        let mut array = vec![&taro, &hanako, &kyoko, &ryosuke];
+
+        let expected = vec![&ryosuke, &kyoko, &hanako, &taro];
+
+        let comparator = by_key(|s: &&Student| s.last_name.clone())
+            .then_by(by_key(|s: &&Student| s.first_name.clone()));
+        assert!(third::sort_by(&mut array, &comparator).is_ok());
+
+        assert_eq!(array, expected);
+    }
+
+    // キーごとにソート順を指定する
+    #[test]
+    fn sort_students_by_last_name_desc_first_name_asc() {
+        let taro = Student::new("Taro", "Yamada", Some(16));
+        let hanako = Student::new("Hanako", "Yamada", Some(14));
+        let kyoko = Student::new("Kyoko", "Ito", Some(15));
+        let ryosuke = Student::new("Ryosuke", "Hayashi", Some(17));
+
+        let mut array = vec![&taro, &hanako, &kyoko, &ryosuke];
+
+        let expected = vec![&hanako, &taro, &kyoko, &ryosuke];
+
+        let comparator = by_key(|s: &&Student| s.last_name.clone())
+            .with_order(&Descending)
+            .then_by(by_key(|s: &&Student| s.first_name.clone()).with_order(&Ascending));
+        assert!(fourth::sort_by(&mut array, &comparator).is_ok());
+
+        assert_eq!(array, expected);
+    }
+
+    #[test]
+    fn sort_students_by_age_nulls_first() {
+        let taro = Student::new("Taro", "Yamada", Some(16));
+        let hanako = Student::new("Hanako", "Yamada", None);
+        let kyoko = Student::new("Kyoko", "Ito", Some(15));
+        let ryosuke = Student::new("Ryosuke", "Hayashi", None);
+
+        let mut array = vec![&taro, &hanako, &kyoko, &ryosuke];
+
+        let expected = vec![&ryosuke, &hanako, &kyoko, &taro];
+
+        let comparator = nulls_first(|s: &&Student| s.age)
+            .then_by(by_key(|s: &&Student| s.first_name.clone()).reverse());
+        assert!(fourth::sort_by(&mut array, &comparator).is_ok());
+
+        assert_eq!(array, expected);
+    }
+
+    #[test]
+    fn sort_students_by_age_nulls_last() {
+        let taro = Student::new("Taro", "Yamada", Some(16));
+        let hanako = Student::new("Hanako", "Yamada", None);
+        let kyoko = Student::new("Kyoko", "Ito", Some(15));
+        let ryosuke = Student::new("Ryosuke", "Hayashi", Some(17));
+
+        let mut array = vec![&taro, &hanako, &kyoko, &ryosuke];
+
+        let expected = vec![&kyoko, &taro, &ryosuke, &hanako];
+
+        assert!(third::sort_by(&mut array, &nulls_last(|s: &&Student| s.age)).is_ok());
+
+        assert_eq!(array, expected);
+
+        // 降順にしてもNoneは末尾のまま
+        let expected = vec![&ryosuke, &taro, &kyoko, &hanako];
+
+        let comparator = nulls_last_with_order(|s: &&Student| s.age, &Descending);
+        assert!(third::sort_by(&mut array, &comparator).is_ok());
+
+        assert_eq!(array, expected);
+    }
+
+    #[test]
+    fn sort_students_by_age_desc_nulls_first() {
+        let taro = Student::new("Taro", "Yamada", Some(16));
+        let hanako = Student::new("Hanako", "Yamada", None);
+        let kyoko = Student::new("Kyoko", "Ito", Some(15));
+        let ryosuke = Student::new("Ryosuke", "Hayashi", Some(17));
+
+        let mut array = vec![&taro, &hanako, &kyoko, &ryosuke];
+
+        let expected = vec![&hanako, &ryosuke, &taro, &kyoko];
+
+        let comparator = nulls_first_with_order(|s: &&Student| s.age, &Descending);
+        assert!(fourth::sort_by(&mut array, &comparator).is_ok());
+
+        assert_eq!(array, expected);
+    }
+}
@@ -0,0 +1,177 @@
+use caseless::Caseless;
+use std::cmp::Ordering;
+use std::cmp::Ordering::*;
+use std::iter::Peekable;
+use std::str::Chars;
+
+// case_folded以外の比較は、キーが等しい場合は元の文字列のバイト列で比較する
+// そのため"a"と"A"のように大文字と小文字だけが異なる文字列も、常に同じ順に並ぶ
+
+/// 文字列に含まれる数字の並びを数として比較する
+/// "file2"は"file10"より前になる。数としては等しい"01"と"1"は、バイト列で比較する
+/// # 引数
+/// - a, b : 比較する文字列。third::sort_byやfourth::sort_byにそのまま渡せる
+pub fn natural<S: AsRef<str> + ?Sized>(a: &S, b: &S) -> Ordering {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    let (mut x, mut y) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let ordering = match (x.peek(), y.peek()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Less,
+            (Some(_), None) => return Greater,
+            (Some(c), Some(d)) if c.is_ascii_digit() && d.is_ascii_digit() => {
+                cmp_digits(&take_digits(&mut x), &take_digits(&mut y))
+            }
+            (Some(&c), Some(&d)) => {
+                x.next();
+                y.next();
+                c.cmp(&d)
+            }
+        };
+        if ordering != Equal {
+            return ordering;
+        }
+    }
+}
+
+/// ASCIIの大文字と小文字を区別せずに比較する
+/// # 引数
+/// - a, b : 比較する文字列。third::sort_byやfourth::sort_byにそのまま渡せる
+pub fn ascii_case_insensitive<S: AsRef<str> + ?Sized>(a: &S, b: &S) -> Ordering {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    a.bytes()
+        .map(|c| c.to_ascii_lowercase())
+        .cmp(b.bytes().map(|c| c.to_ascii_lowercase()))
+        .then_with(|| a.cmp(b))
+}
+
+/// Unicodeのケースフォールディング（CaseFolding.txtの完全な対応）をしてから比較する
+/// "ß"と"SS"や、"ς"と"σ"のように大文字と小文字だけが異なる文字列は等しいものとして扱う
+/// ほかの比較と異なり、フォールディングした結果が等しい場合はバイト列で比較せず、Equalを返す
+/// # 引数
+/// - a, b : 比較する文字列。third::sort_byやfourth::sort_byにそのまま渡せる
+pub fn case_folded<S: AsRef<str> + ?Sized>(a: &S, b: &S) -> Ordering {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    a.chars()
+        .default_case_fold()
+        .cmp(b.chars().default_case_fold())
+}
+
+/// 先頭から続く数字をすべて取り出す
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+/// 数字の並びを数として比較する。桁数に制限はない
+fn cmp_digits(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ascii_case_insensitive, case_folded, natural};
+    use crate::{fourth, third};
+    use std::cmp::Ordering::*;
+
+    #[test]
+    fn natural_order() {
+        assert_eq!(natural("file2", "file10"), Less);
+        assert_eq!(natural("file10", "file10"), Equal);
+        assert_eq!(natural("file10b", "file10a"), Greater);
+        assert_eq!(
+            natural("a99999999999999999999", "a100000000000000000000"),
+            Less
+        );
+        // 数としては等しい場合
+        assert_eq!(natural("x01", "x1"), Less);
+        assert_eq!(natural("x01y", "x1z"), Less);
+    }
+
+    #[test]
+    fn sort_files_naturally() {
+        let mut x = vec![
+            "file10.txt",
+            "file2.txt",
+            "file1.txt",
+            "File3.txt",
+            "file1a.txt",
+            "file01.txt",
+            "img12",
+            "img2",
+        ];
+
+        assert!(third::sort_by(&mut x, &natural).is_ok());
+
+        assert_eq!(
+            x,
+            vec![
+                "File3.txt",
+                "file01.txt",
+                "file1.txt",
+                "file1a.txt",
+                "file2.txt",
+                "file10.txt",
+                "img2",
+                "img12",
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_ascii_case_insensitive() {
+        let mut x: Vec<String> = vec!["banana", "Apple", "apple", "Cherry"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        assert!(fourth::sort_by(&mut x, &ascii_case_insensitive).is_ok());
+
+        assert_eq!(x, vec!["Apple", "apple", "banana", "Cherry"]);
+    }
+
+    #[test]
+    fn case_folded_equal() {
+        assert_eq!(case_folded("ß", "SS"), Equal);
+        assert_eq!(case_folded("straße", "STRASSE"), Equal);
+        assert_eq!(case_folded("ς", "σ"), Equal);
+        assert_eq!(case_folded("ΣΊΣΥΦΟΣ", "σίσυφος"), Equal);
+        assert_eq!(case_folded("Äpfel", "äpfel"), Equal);
+        assert_eq!(case_folded("apfel", "Zebra"), Less);
+        assert_eq!(case_folded("strasse", "Straßenbahn"), Less);
+    }
+
+    #[test]
+    fn sort_case_folded() {
+        let mut x = vec![
+            "Zebra",
+            "Straßenbahn",
+            "apfel",
+            "Σίσυφος",
+            "STRASSE",
+            "ß",
+            "Ω",
+            "äpfel",
+        ];
+
+        assert!(fourth::sort_by(&mut x, &case_folded).is_ok());
+
+        assert_eq!(
+            x,
+            vec![
+                "apfel",
+                "ß",
+                "STRASSE",
+                "Straßenbahn",
+                "Zebra",
+                "äpfel",
+                "Σίσυφος",
+                "Ω"
+            ]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::Relaxed};
+
+/// 実行中のソートを中断したり、進み具合を調べたりするためのハンドル
+/// 別のスレッドと共有し、ソートしている間にcancelやpercentを呼び出せる
+/// 中断の要求は取り消せないので、ソートごとに新しいハンドルを作る
+#[derive(Debug, Default)]
+pub struct SortControl {
+    cancelled: AtomicBool,
+    /// 1層あたりの比較の回数（要素数n / 2）
+    width: AtomicUsize,
+    /// 全体の比較の回数
+    total: AtomicUsize,
+    /// 終わった比較の回数
+    done: AtomicUsize,
+}
+
+impl SortControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ソートの中断を要求する。ソートは次の段階に進む前に中断する
+    /// 要求は取り消されないので、このハンドルを使うこれからのソートもすぐに中断する
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Relaxed);
+    }
+
+    /// 中断が要求されているかどうかを返す
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Relaxed)
+    }
+
+    /// 進み具合を百分率で返す
+    pub fn percent(&self) -> f64 {
+        match self.total.load(Relaxed) {
+            0 => 100.0,
+            total => self.done.load(Relaxed) as f64 * 100.0 / total as f64,
+        }
+    }
+
+    /// 終わった比較器の層の数を返す
+    pub fn completed_layers(&self) -> usize {
+        match self.width.load(Relaxed) {
+            0 => 0,
+            width => self.done.load(Relaxed) / width,
+        }
+    }
+
+    /// 比較器の層の総数を返す
+    pub fn total_layers(&self) -> usize {
+        match self.width.load(Relaxed) {
+            0 => 0,
+            width => self.total.load(Relaxed) / width,
+        }
+    }
+
+    /// 要素数nの配列のソートを始める。進み具合を0に戻す
+    /// ソートを始める前に要求された中断を失わないよう、中断の要求は戻さない
+    /// # 引数
+    /// - n : 配列の要素数。2^nでなければならない
+    pub(crate) fn start(&self, n: usize) {
+        let bits = n.trailing_zeros() as usize;
+        let width = n / 2;
+        self.width.store(width, Relaxed);
+        self.total.store(width * bits * (bits + 1) / 2, Relaxed);
+        self.done.store(0, Relaxed);
+    }
+
+    /// 比較が終わったことを記録する
+    /// # 引数
+    /// - comparisons : 終わった比較の回数
+    pub(crate) fn advance(&self, comparisons: usize) {
+        self.done.fetch_add(comparisons, Relaxed);
+    }
+
+    /// すべての比較が終わったかどうかを返す
+    pub(crate) fn is_completed(&self) -> bool {
+        self.done.load(Relaxed) >= self.total.load(Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SortControl;
+
+    #[test]
+    fn progress() {
+        let control = SortControl::new();
+        assert_eq!(control.percent(), 100.0);
+
+        // 要素数16の場合、4 * 5 / 2 = 10層、1層あたり8回の比較
+        control.start(16);
+        assert_eq!(control.total_layers(), 10);
+        assert_eq!(control.percent(), 0.0);
+
+        control.advance(8 * 5);
+        assert_eq!(control.completed_layers(), 5);
+        assert_eq!(control.percent(), 50.0);
+    }
+
+    #[test]
+    fn cancel() {
+        let control = SortControl::new();
+        assert!(!control.is_cancelled());
+
+        control.cancel();
+        assert!(control.is_cancelled());
+    }
+}
@@ -0,0 +1,517 @@
+use crate::fourth;
+use crate::merge::LoserTree;
+use crate::SortOrder;
+use crate::SortOrder::*;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// 圧縮したランの1ブロックに含める要素の最大数
+const BLOCK_LEN: usize = 4096;
+/// マニフェストのファイル名
+const MANIFEST: &str = "manifest";
+/// マニフェストの1行目
+const MANIFEST_VERSION: &str = "bitonic-sorter-manifest 1";
+
+/// メモリに収まらないu64の配列のファイルをソートする
+/// 入力をランに分けてfourth::sortでソートし、圧縮してスピル用のディレクトリに書き出してから併合する
+/// 書き出したランはマニフェストに記録するので、中断しても同じ設定で再び実行すれば続きから再開できる
+#[derive(Debug, Clone)]
+pub struct ExternalSort {
+    spill_dir: PathBuf,
+    run_len: usize,
+}
+
+impl ExternalSort {
+    /// 外部ソートの設定を作る。run_lenが2^nではない場合、エラーを返す
+    /// # 引数
+    /// - spill_dir : ランとマニフェストを書き出すディレクトリ。存在しない場合は作る
+    /// - run_len : 1つのランの要素数。ただし、2^nでなければならない。メモリに収まる大きさにする
+    pub fn new<P: AsRef<Path>>(spill_dir: P, run_len: usize) -> Result<Self, String> {
+        if !run_len.is_power_of_two() {
+            return Err(format!(
+                "The run length is not a power of two. (run_len: {})",
+                run_len
+            ));
+        }
+        Ok(Self {
+            spill_dir: spill_dir.as_ref().to_path_buf(),
+            run_len,
+        })
+    }
+
+    /// リトルエンディアンのu64を並べたファイルをソートし、同じ形式で書き出す
+    /// 終わったら、スピル用のディレクトリのランとマニフェストを削除する
+    /// # 引数
+    /// - input : 入力ファイルのパス
+    /// - output : 出力ファイルのパス
+    /// - order : ソート順
+    pub fn sort_file<P, Q>(&self, input: P, output: Q, order: &SortOrder) -> Result<(), String>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        self.generate_runs(&input, order)?;
+        let manifest = self.load_manifest(&self.header(input.as_ref(), order)?);
+
+        let runs = manifest
+            .iter()
+            .map(|run| self.run_path(run.index))
+            .collect::<Vec<_>>();
+        merge_runs(&runs, output.as_ref(), order)
+            .map_err(|e| format!("{}: {}", output.as_ref().display(), e))?;
+
+        for run in &runs {
+            fs::remove_file(run).map_err(|e| format!("{}: {}", run.display(), e))?;
+        }
+        // 入力が空の場合はランを書き出さないので、マニフェストもない
+        let manifest = self.spill_dir.join(MANIFEST);
+        match fs::remove_file(&manifest) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(format!("{}: {}", manifest.display(), e))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// まだ書き出していないランをソートして書き出す
+    /// マニフェストが同じ入力と設定のものであれば、記録済みのランは読み直さない
+    /// # 引数
+    /// - input : 入力ファイルのパス
+    /// - order : ソート順
+    /// # 戻り値
+    /// - 新たに書き出したランの数
+    pub fn generate_runs<P: AsRef<Path>>(
+        &self,
+        input: P,
+        order: &SortOrder,
+    ) -> Result<usize, String> {
+        let input = input.as_ref();
+        let header = self.header(input, order)?;
+        fs::create_dir_all(&self.spill_dir)
+            .map_err(|e| format!("{}: {}", self.spill_dir.display(), e))?;
+
+        let mut runs = self.load_manifest(&header);
+        let total = (input_len(input)? / 8).div_ceil(self.run_len as u64) as usize;
+
+        let mut file = File::open(input).map_err(|e| format!("{}: {}", input.display(), e))?;
+        let mut generated = 0;
+        for index in 0..total {
+            if runs.iter().any(|run| run.index == index) {
+                continue;
+            }
+
+            let count = read_run(&mut file, index, self.run_len, order)
+                .and_then(|values| {
+                    write_run(&self.run_path(index), &values)?;
+                    Ok(values.len())
+                })
+                .map_err(|e| format!("{}: {}", input.display(), e))?;
+            runs.push(Run { index, count });
+            self.save_manifest(&header, &runs)?;
+            generated += 1;
+        }
+        Ok(generated)
+    }
+
+    fn run_path(&self, index: usize) -> PathBuf {
+        self.spill_dir.join(format!("run-{:06}.bin", index))
+    }
+
+    /// マニフェストの先頭部分。入力や設定が変わった場合、記録済みのランは使えない
+    fn header(&self, input: &Path, order: &SortOrder) -> Result<String, String> {
+        let metadata = fs::metadata(input).map_err(|e| format!("{}: {}", input.display(), e))?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_nanos());
+        Ok(format!(
+            "{}\ninput {}\nlength {}\nmodified {}\nrun_len {}\norder {}\n",
+            MANIFEST_VERSION,
+            input.display(),
+            metadata.len(),
+            modified,
+            self.run_len,
+            match *order {
+                Ascending => "asc",
+                Descending => "desc",
+            }
+        ))
+    }
+
+    /// マニフェストを読み、書き出し済みのランを返す
+    /// マニフェストがない場合や、先頭部分が一致しない場合は、空を返して最初からやり直す
+    fn load_manifest(&self, header: &str) -> Vec<Run> {
+        let text = match fs::read_to_string(self.spill_dir.join(MANIFEST)) {
+            Ok(text) => text,
+            Err(_) => return Vec::new(),
+        };
+        let body = match text.strip_prefix(header) {
+            Some(body) => body,
+            None => return Vec::new(),
+        };
+
+        body.lines()
+            .filter_map(|line| {
+                let mut words = line.split_whitespace();
+                match (words.next(), words.next(), words.next()) {
+                    (Some("run"), Some(index), Some(count)) => Some(Run {
+                        index: index.parse().ok()?,
+                        count: count.parse().ok()?,
+                    }),
+                    _ => None,
+                }
+            })
+            .filter(|run| self.run_path(run.index).is_file())
+            .collect()
+    }
+
+    /// マニフェストを一時ファイルに書いてから置き換える。途中で中断しても古いマニフェストが残る
+    fn save_manifest(&self, header: &str, runs: &[Run]) -> Result<(), String> {
+        let mut text = header.to_string();
+        for run in runs {
+            text.push_str(&format!("run {} {}\n", run.index, run.count));
+        }
+        let path = self.spill_dir.join(MANIFEST);
+        write_atomic(&path, text.as_bytes()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// 書き出し済みのラン
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Run {
+    index: usize,
+    count: usize,
+}
+
+fn input_len(input: &Path) -> Result<u64, String> {
+    let len = fs::metadata(input)
+        .map_err(|e| format!("{}: {}", input.display(), e))?
+        .len();
+    if len % 8 != 0 {
+        return Err(format!(
+            "{}: The file length is not a multiple of 8. (length: {})",
+            input.display(),
+            len
+        ));
+    }
+    Ok(len)
+}
+
+/// 入力のindex番目のランを読み、ソートして返す
+/// 最後のランの要素数が2^nではない場合、末尾に来る値を詰めてソートしてから取り除く
+fn read_run(
+    file: &mut File,
+    index: usize,
+    run_len: usize,
+    order: &SortOrder,
+) -> io::Result<Vec<u64>> {
+    file.seek(SeekFrom::Start((index * run_len * 8) as u64))?;
+    let mut bytes = Vec::with_capacity(run_len * 8);
+    file.take((run_len * 8) as u64).read_to_end(&mut bytes)?;
+
+    let mut values: Vec<u64> = bytes
+        .chunks_exact(8)
+        .map(|b| u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+        .collect();
+    let count = values.len();
+    // 詰めた値は末尾に来る。元からある同じ値とは区別する必要がない
+    let padding = match *order {
+        Ascending => u64::MAX,
+        Descending => u64::MIN,
+    };
+    values.resize(count.next_power_of_two(), padding);
+    fourth::sort(&mut values, order).map_err(io::Error::other)?;
+    values.truncate(count);
+    Ok(values)
+}
+
+/// ソートしたランを圧縮して書き出す
+/// ブロックごとに、要素数、先頭の値、隣り合う値の差をzigzag符号化したものを可変長整数で書く
+fn write_run(path: &Path, values: &[u64]) -> io::Result<()> {
+    let mut bytes = Vec::new();
+    for block in values.chunks(BLOCK_LEN) {
+        write_varint(&mut bytes, block.len() as u64);
+        let mut previous = 0u64;
+        for &value in block {
+            let delta = value.wrapping_sub(previous) as i64;
+            write_varint(&mut bytes, ((delta << 1) ^ (delta >> 63)) as u64);
+            previous = value;
+        }
+    }
+    write_atomic(path, &bytes)
+}
+
+/// 一時ファイルに書いて同期してから、名前を変えて置き換える
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let temp = path.with_extension("tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<Option<u64>> {
+    let mut value = 0u64;
+    let mut byte = [0u8];
+    for shift in (0..64).step_by(7) {
+        if reader.read(&mut byte)? == 0 {
+            return if shift == 0 {
+                Ok(None)
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "truncated varint",
+                ))
+            };
+        }
+        value |= u64::from(byte[0] & 0x7F) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint is too long",
+    ))
+}
+
+/// 圧縮したランを1ブロックずつ読む
+struct RunReader {
+    reader: BufReader<File>,
+    block: Vec<u64>,
+    position: usize,
+}
+
+impl RunReader {
+    fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(File::open(path)?),
+            block: Vec::with_capacity(BLOCK_LEN),
+            position: 0,
+        })
+    }
+
+    fn read_block(&mut self) -> io::Result<bool> {
+        let len = match read_varint(&mut self.reader)? {
+            Some(len) => len as usize,
+            None => return Ok(false),
+        };
+        self.block.clear();
+        self.position = 0;
+        let mut previous = 0u64;
+        for _ in 0..len {
+            let zigzag = read_varint(&mut self.reader)?
+                .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "truncated block"))?;
+            let delta = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+            previous = previous.wrapping_add(delta as u64);
+            self.block.push(previous);
+        }
+        Ok(true)
+    }
+
+    fn next_value(&mut self) -> io::Result<Option<u64>> {
+        while self.position == self.block.len() {
+            if !self.read_block()? {
+                return Ok(None);
+            }
+        }
+        self.position += 1;
+        Ok(Some(self.block[self.position - 1]))
+    }
+}
+
+/// ランを併合して出力ファイルに書き出す
+fn merge_runs(runs: &[PathBuf], output: &Path, order: &SortOrder) -> io::Result<()> {
+    let mut readers = runs
+        .iter()
+        .map(|run| RunReader::open(run))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let heads = readers
+        .iter_mut()
+        .map(RunReader::next_value)
+        .collect::<io::Result<Vec<_>>>()?;
+    let forward = *order == Ascending;
+    let mut tree = LoserTree::new(
+        heads,
+        |a: &u64, b: &u64| {
+            if forward {
+                a.cmp(b)
+            } else {
+                b.cmp(a)
+            }
+        },
+    );
+
+    let mut out = BufWriter::new(File::create(output)?);
+    while let Some((i, _)) = tree.peek() {
+        let next = readers[i].next_value()?;
+        if let Some(value) = tree.replace(next) {
+            out.write_all(&value.to_le_bytes())?;
+        }
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_varint, write_run, write_varint, ExternalSort, RunReader, MANIFEST};
+    use crate::utils::new_u32_vec;
+    use crate::SortOrder::*;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    /// テストごとに異なる一時ディレクトリを作る
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("bitonic-sorter-{}-{}", process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_values(path: &PathBuf, values: &[u64]) {
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        fs::write(path, bytes).unwrap();
+    }
+
+    fn read_values(path: &PathBuf) -> Vec<u64> {
+        fs::read(path)
+            .unwrap()
+            .chunks_exact(8)
+            .map(|b| u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+            .collect()
+    }
+
+    fn new_u64_vec(n: usize) -> Vec<u64> {
+        new_u32_vec(n)
+            .into_iter()
+            .map(|v| u64::from(v) * 3 + u64::from(v >> 30) * (u64::MAX / 4))
+            .collect()
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        let mut bytes = Vec::new();
+        for &value in &[0, 1, 127, 128, 300, u64::MAX] {
+            write_varint(&mut bytes, value);
+        }
+
+        let mut reader = &bytes[..];
+        for &value in &[0, 1, 127, 128, 300, u64::MAX] {
+            assert_eq!(read_varint(&mut reader).unwrap(), Some(value));
+        }
+        assert_eq!(read_varint(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn compressed_run_round_trip() {
+        let dir = temp_dir("run");
+        let path = dir.join("run.bin");
+        let mut values = new_u64_vec(10_000);
+        values.sort();
+        values.push(0);
+
+        write_run(&path, &values).unwrap();
+        let size = fs::metadata(&path).unwrap().len();
+        let mut reader = RunReader::open(&path).unwrap();
+        let mut actual = Vec::new();
+        while let Some(value) = reader.next_value().unwrap() {
+            actual.push(value);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(actual, values);
+        assert!(size < (values.len() * 8) as u64);
+    }
+
+    #[test]
+    fn sort_file_ascending() {
+        let dir = temp_dir("ascending");
+        let (input, output) = (dir.join("input"), dir.join("output"));
+        let mut values = new_u64_vec(10_000);
+        write_values(&input, &values);
+
+        let sorter = ExternalSort::new(dir.join("spill"), 1024).unwrap();
+        let result = sorter.sort_file(&input, &output, &Ascending);
+
+        let actual = read_values(&output);
+        let spill_is_empty = fs::read_dir(dir.join("spill")).unwrap().next().is_none();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result, Ok(()));
+        values.sort();
+        assert_eq!(actual, values);
+        assert!(spill_is_empty);
+    }
+
+    #[test]
+    fn sort_empty_file() {
+        let dir = temp_dir("empty");
+        let (input, output) = (dir.join("input"), dir.join("output"));
+        write_values(&input, &[]);
+
+        let sorter = ExternalSort::new(dir.join("spill"), 1024).unwrap();
+        let result = sorter.sort_file(&input, &output, &Ascending);
+
+        let actual = read_values(&output);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result, Ok(()));
+        assert!(actual.is_empty());
+    }
+
+    // 途中で中断した後に再開する
+    #[test]
+    fn resume_from_manifest() {
+        let dir = temp_dir("resume");
+        let (input, output) = (dir.join("input"), dir.join("output"));
+        let mut values = new_u64_vec(5000);
+        write_values(&input, &values);
+
+        let sorter = ExternalSort::new(dir.join("spill"), 1024).unwrap();
+        assert_eq!(sorter.generate_runs(&input, &Descending), Ok(5));
+        assert_eq!(sorter.generate_runs(&input, &Descending), Ok(0));
+
+        // 最後のランを記録する前に中断したことにする
+        let manifest = dir.join("spill").join(MANIFEST);
+        let text = fs::read_to_string(&manifest).unwrap();
+        let text: Vec<&str> = text.lines().collect();
+        fs::write(&manifest, text[..text.len() - 1].join("\n") + "\n").unwrap();
+        assert_eq!(sorter.generate_runs(&input, &Descending), Ok(1));
+
+        // ソート順が変わった場合は最初からやり直す
+        assert_eq!(sorter.generate_runs(&input, &Ascending), Ok(5));
+
+        let result = sorter.sort_file(&input, &output, &Descending);
+
+        let actual = read_values(&output);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result, Ok(()));
+        values.sort_by(|a, b| b.cmp(a));
+        assert_eq!(actual, values);
+    }
+
+    #[test]
+    fn invalid_input() {
+        let dir = temp_dir("invalid");
+        let input = dir.join("input");
+        fs::write(&input, [0u8; 12]).unwrap();
+
+        assert!(ExternalSort::new(dir.join("spill"), 1000).is_err());
+        let sorter = ExternalSort::new(dir.join("spill"), 1024).unwrap();
+        let result = sorter.sort_file(&input, dir.join("output"), &Ascending);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -1,10 +1,19 @@
+use crate::checked::{Checker, Inconsistency};
+use crate::control::SortControl;
+use crate::hybrid::{merge_small, sort_small, BaseCase};
+use crate::utils::apply_indices;
+use crate::SortError;
 use crate::SortOrder;
 use crate::SortOrder::*;
-use rayon;
+use rayon::prelude::*;
 use std::cmp::Ordering;
 use std::cmp::Ordering::*;
+use std::convert::Infallible;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use std::sync::Mutex;
 
-const PARALLEL_THRESHOLD: usize = 4096;
+pub(crate) const PARALLEL_THRESHOLD: usize = 4096;
 
 /// 配列をソートする。配列の要素が2^nではない場合、エラーを返す
 /// # 引数
@@ -12,8 +21,8 @@ const PARALLEL_THRESHOLD: usize = 4096;
 /// - order : ソート順
 pub fn sort<T: Ord + Send>(array: &mut [T], order: &SortOrder) -> Result<(), String> {
     match *order {
-        Ascending => sort_by(array, &|a, b| a.cmp(&b)),
-        Descending => sort_by(array, &|a, b| b.cmp(&a)),
+        Ascending => sort_by(array, &|a, b| a.cmp(b)),
+        Descending => sort_by(array, &|a, b| b.cmp(a)),
     }
 }
 
@@ -23,7 +32,115 @@ pub fn sort<T: Ord + Send>(array: &mut [T], order: &SortOrder) -> Result<(), Str
 /// - comparator : 大小比較するためのクロージャ
 /// # 戻り値
 /// - 要素数が2^nの場合Okを、それ以外の場合Errを返す
+/// # パニック
+/// - comparatorがパニックした場合、並列に処理している他の部分が終わるのを待ってからパニックする
+///   要素は入れ替えるだけなので、パニックした後も配列は入力の要素を並べ替えたものになっている
 pub fn sort_by<T, F>(array: &mut [T], comparator: &F) -> Result<(), String>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    sort_hybrid_by(array, comparator, &BaseCase::Recursive)
+}
+
+/// 配列をソートする。比較のためのクロージャがパニックした場合、パニックを捕まえてエラーを返す
+/// パニックした場合も、配列は入力の要素を並べ替えたものになっている
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ
+/// # 戻り値
+/// - ソートできた場合Okを、要素数が2^nではない場合やcomparatorがパニックした場合Errを返す
+pub fn sort_by_catch_unwind<T, F>(array: &mut [T], comparator: &F) -> Result<(), SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    match array.len() {
+        0 => Ok(()),
+        n => {
+            if n.is_power_of_two() {
+                // 要素を入れ替えるだけなので、パニックしても配列が壊れることはない
+                panic::catch_unwind(AssertUnwindSafe(|| {
+                    do_sort(array, comparator, true, &BaseCase::Recursive)
+                }))
+                .map_err(|_| SortError::ComparatorPanicked)
+            } else {
+                Err(SortError::NotPowerOfTwo(n))
+            }
+        }
+    }
+}
+
+/// キーを比較して配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// キーは比較のたびに計算される
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - key : 要素からキーを取り出すクロージャ。キーはOrdを実装しなければならない。
+/// - order : ソート順
+pub fn sort_by_key<T, K, F>(array: &mut [T], key: &F, order: &SortOrder) -> Result<(), String>
+where
+    T: Send,
+    K: Ord,
+    F: Sync + Fn(&T) -> K,
+{
+    match *order {
+        Ascending => sort_by(array, &|a, b| key(a).cmp(&key(b))),
+        Descending => sort_by(array, &|a, b| key(b).cmp(&key(a))),
+    }
+}
+
+/// キーを比較して配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// キーは各要素につき1回だけ計算し、キーと添字の組をソートしてから配列を並べ替える
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - key : 要素からキーを取り出すクロージャ。キーはOrdを実装しなければならない。
+/// - order : ソート順
+pub fn sort_by_cached_key<T, K, F>(
+    array: &mut [T],
+    key: &F,
+    order: &SortOrder,
+) -> Result<(), String>
+where
+    T: Send,
+    K: Ord + Send,
+    F: Fn(&T) -> K,
+{
+    let mut keys: Vec<(K, usize)> = array.iter().map(key).zip(0..).collect();
+    sort(&mut keys, order)?;
+
+    let mut indices: Vec<usize> = keys.into_iter().map(|(_, i)| i).collect();
+    apply_indices(array, &mut indices);
+    Ok(())
+}
+
+/// 小さな部分配列を指定の方法でソートしながら、配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。また、要素はOrdを実装しなければならない。
+/// - order : ソート順
+/// - base_case : 小さな部分配列のソート方法
+pub fn sort_hybrid<T: Ord + Send>(
+    array: &mut [T],
+    order: &SortOrder,
+    base_case: &BaseCase,
+) -> Result<(), String> {
+    match *order {
+        Ascending => sort_hybrid_by(array, &|a, b| a.cmp(b), base_case),
+        Descending => sort_hybrid_by(array, &|a, b| b.cmp(a), base_case),
+    }
+}
+
+/// 小さな部分配列を指定の方法でソートしながら、配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ
+/// - base_case : 小さな部分配列のソート方法
+/// # 戻り値
+/// - 要素数が2^nの場合Okを、それ以外の場合Errを返す
+pub fn sort_hybrid_by<T, F>(
+    array: &mut [T],
+    comparator: &F,
+    base_case: &BaseCase,
+) -> Result<(), String>
 where
     T: Send,
     F: Sync + Fn(&T, &T) -> Ordering,
@@ -32,7 +149,7 @@ where
         0 => Ok(()),
         n => {
             if n.is_power_of_two() {
-                do_sort(array, comparator, true);
+                do_sort(array, comparator, true, base_case);
                 Ok(())
             } else {
                 Err(format!(
@@ -44,17 +161,213 @@ where
     }
 }
 
+/// 失敗する可能性のある比較で配列をソートする
+/// 比較がエラーを返した時点でソートを中断し、そのエラーを返す
+/// 並列に処理している他の部分も、次の比較の前に中断する
+/// 中断した場合も、配列は入力の要素を並べ替えたものになっている
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ。比較できない場合はErrを返す
+/// # 戻り値
+/// - ソートできた場合Okを、要素数が2^nではない場合や比較がエラーを返した場合Errを返す
+pub fn try_sort_by<T, E, F>(array: &mut [T], comparator: &F) -> Result<(), SortError<E>>
+where
+    T: Send,
+    E: Send,
+    F: Sync + Fn(&T, &T) -> Result<Ordering, E>,
+{
+    match array.len() {
+        0 => Ok(()),
+        n => {
+            if n.is_power_of_two() {
+                let abort = Abort::new(None);
+                try_do_sort(array, comparator, true, &abort);
+                match abort.into_error() {
+                    Some(e) => Err(SortError::Comparator(e)),
+                    None => Ok(()),
+                }
+            } else {
+                Err(SortError::NotPowerOfTwo(n))
+            }
+        }
+    }
+}
+
+/// 中断と進み具合の確認ができるように配列をソートする
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。また、要素はOrdを実装しなければならない。
+/// - order : ソート順
+/// - control : 中断と進み具合の確認のためのハンドル
+pub fn sort_with_control<T: Ord + Send>(
+    array: &mut [T],
+    order: &SortOrder,
+    control: &SortControl,
+) -> Result<(), SortError> {
+    match *order {
+        Ascending => sort_by_with_control(array, &|a, b| a.cmp(b), control),
+        Descending => sort_by_with_control(array, &|a, b| b.cmp(a), control),
+    }
+}
+
+/// 中断と進み具合の確認ができるように配列をソートする
+/// controlのcancelが呼ばれると、次の比較の前に中断してエラーを返す
+/// すべての比較が終わった後にcancelが呼ばれた場合は、ソートできたものとしてOkを返す
+/// 中断した場合も、配列は入力の要素を並べ替えたものになっている
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ
+/// - control : 中断と進み具合の確認のためのハンドル
+/// # 戻り値
+/// - ソートできた場合Okを、要素数が2^nではない場合や中断された場合Errを返す
+pub fn sort_by_with_control<T, F>(
+    array: &mut [T],
+    comparator: &F,
+    control: &SortControl,
+) -> Result<(), SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    match array.len() {
+        0 => Ok(()),
+        n => {
+            if n.is_power_of_two() {
+                control.start(n);
+                let abort = Abort::<Infallible>::new(Some(control));
+                try_do_sort(array, &|a, b| Ok(comparator(a, b)), true, &abort);
+                if !control.is_completed() {
+                    Err(SortError::Cancelled)
+                } else {
+                    Ok(())
+                }
+            } else {
+                Err(SortError::NotPowerOfTwo(n))
+            }
+        }
+    }
+}
+
+/// 比較のためのクロージャを検査しながら配列をソートする。デバッグ用
+/// ソート中の比較を抜き取って反対称律と推移律を検査し、ソート後にソートされているかを検査する
+/// 矛盾が見つかった場合、配列はソート前のまま変更されない
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ
+/// # 戻り値
+/// - ソートできた場合Okを、要素数が2^nではない場合や矛盾が見つかった場合Errを返す
+pub fn checked_sort_by<T, F>(
+    array: &mut [T],
+    comparator: &F,
+) -> Result<(), SortError<Inconsistency>>
+where
+    T: Send + Sync,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    // 要素の代わりに添字をソートし、矛盾の原因となった要素をソート前の位置で示せるようにする
+    let mut indices: Vec<usize> = (0..array.len()).collect();
+    {
+        let checker = Checker::new(array, comparator);
+        try_sort_by(&mut indices, &|&a, &b| checker.compare(a, b))?;
+        checker
+            .verify_sorted(&indices)
+            .map_err(SortError::Comparator)?;
+    }
+    apply_indices(array, &mut indices);
+    Ok(())
+}
+
+/// 配列をソートしたときの並びを、元の配列の添字の列として返す。配列そのものは変更しない
+/// キーと添字の組をソートし、キーが等しい場合は添字の小さい方を先にする
+/// # 引数
+/// - keys : キーの配列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ
+/// # 戻り値
+/// - ソート後の各位置に来る要素の元の添字。要素数が2^nではない場合Err
+pub fn argsort_by<T, F>(keys: &[T], comparator: &F) -> Result<Vec<usize>, String>
+where
+    T: Sync,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let mut pairs: Vec<(&T, usize)> = keys.iter().zip(0..).collect();
+    sort_by(&mut pairs, &|a, b| comparator(a.0, b.0).then(a.1.cmp(&b.1)))?;
+    Ok(pairs.into_iter().map(|(_, i)| i).collect())
+}
+
+/// apply_permutationで並べ替えられる列
+pub trait Column: Send {
+    /// 列の要素数を返す
+    fn len(&self) -> usize;
+
+    /// 列が空かどうかを返す
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// perm[i]番目の要素がi番目に来るように列を並べ替える
+    /// # 引数
+    /// - perm : 並べ替え後の各位置に来る要素の元の添字。列と同じ要素数の順列でなければならない
+    fn gather(&mut self, perm: &[usize]);
+}
+
+impl<T: Send> Column for Vec<T> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    /// 要素を複製せず、入れ替えをたどってその場で並べ替える
+    fn gather(&mut self, perm: &[usize]) {
+        let mut indices = perm.to_vec();
+        apply_indices(self, &mut indices);
+    }
+}
+
+/// 複数の列を同じ順列で並べ替える。列ごとに並列に処理する
+/// argsort_byの結果を渡すと、表の行をキーの列でソートしたのと同じ並びになる
+/// # 引数
+/// - columns : 並べ替える列。要素の型は列ごとに異なってもよい
+/// - perm : 並べ替え後の各位置に来る要素の元の添字
+/// # 戻り値
+/// - permが列と同じ要素数の順列ではない場合、列を変更せずにErrを返す
+pub fn apply_permutation(columns: &mut [&mut dyn Column], perm: &[usize]) -> Result<(), String> {
+    if let Some(column) = columns.iter().find(|column| column.len() != perm.len()) {
+        return Err(format!(
+            "The length of the column is different from the permutation. (column: {}, permutation: {})",
+            column.len(),
+            perm.len()
+        ));
+    }
+
+    let mut seen = vec![false; perm.len()];
+    for &i in perm {
+        match seen.get_mut(i) {
+            Some(seen) if !*seen => *seen = true,
+            _ => {
+                return Err(format!(
+                    "The permutation is out of range or has duplicates. (index: {})",
+                    i
+                ))
+            }
+        }
+    }
+
+    columns
+        .par_iter_mut()
+        .for_each(|column| column.gather(perm));
+    Ok(())
+}
+
 /// 配列をソートする
 /// # 引数
 /// - array : 配列。ただし、要素数は2^nでなければならない
 /// - comparator : 比較のためのクロージャ
 /// - forward : 昇順の場合true, 降順の場合false
-fn do_sort<T, F>(array: &mut [T], comparator: &F, forward: bool)
+/// - base_case : 小さな部分配列のソート方法
+fn do_sort<T, F>(array: &mut [T], comparator: &F, forward: bool, base_case: &BaseCase)
 where
     T: Send,
     F: Sync + Fn(&T, &T) -> Ordering,
 {
-    if array.len() <= 1 {
+    if array.len() <= 1 || sort_small(array, comparator, forward, base_case) {
         return;
     }
 
@@ -65,16 +378,16 @@ where
     if mid >= PARALLEL_THRESHOLD {
         // 並列ソート
         rayon::join(
-            || do_sort(first, comparator, true),
-            || do_sort(second, comparator, false),
+            || do_sort(first, comparator, true, base_case),
+            || do_sort(second, comparator, false, base_case),
         );
     } else {
         // 直列ソート
-        do_sort(first, comparator, true); // 前半を昇順
-        do_sort(second, comparator, false); // 後半を降順でソート
+        do_sort(first, comparator, true, base_case); // 前半を昇順
+        do_sort(second, comparator, false, base_case); // 後半を降順でソート
     }
 
-    sub_sort(array, comparator, forward);
+    sub_sort(array, comparator, forward, base_case);
 }
 
 /// バイトニック列をソートする
@@ -82,12 +395,13 @@ where
 /// - bitonic_array : バイトニック列
 /// - comparator : 比較のためのクロージャ
 /// - forward : 昇順の場合true, 降順の場合false
-fn sub_sort<T, F>(bitonic_array: &mut [T], comparator: &F, forward: bool)
+/// - base_case : 小さな部分配列のソート方法
+fn sub_sort<T, F>(bitonic_array: &mut [T], comparator: &F, forward: bool, base_case: &BaseCase)
 where
     T: Send,
     F: Sync + Fn(&T, &T) -> Ordering,
 {
-    if bitonic_array.len() <= 1 {
+    if bitonic_array.len() <= 1 || merge_small(bitonic_array, comparator, forward, base_case) {
         return;
     }
     // 比較＆入れ替えによって並び順をorderに近づける（ソートは不完全）
@@ -98,12 +412,12 @@ where
 
     if mid >= PARALLEL_THRESHOLD {
         rayon::join(
-            || sub_sort(first, comparator, forward),
-            || sub_sort(second, comparator, forward),
+            || sub_sort(first, comparator, forward, base_case),
+            || sub_sort(second, comparator, forward, base_case),
         );
     } else {
-        sub_sort(first, comparator, forward);
-        sub_sort(second, comparator, forward);
+        sub_sort(first, comparator, forward, base_case);
+        sub_sort(second, comparator, forward, base_case);
     }
 }
 
@@ -134,11 +448,164 @@ where
     }
 }
 
+/// 比較のエラーやSortControlで処理を中断するための状態
+struct Abort<'a, E> {
+    aborted: AtomicBool,
+    error: Mutex<Option<E>>,
+    control: Option<&'a SortControl>,
+}
+
+impl<'a, E> Abort<'a, E> {
+    fn new(control: Option<&'a SortControl>) -> Self {
+        Self {
+            aborted: AtomicBool::new(false),
+            error: Mutex::new(None),
+            control,
+        }
+    }
+
+    /// 中断されているかどうかを返す
+    fn is_aborted(&self) -> bool {
+        self.aborted.load(Relaxed) || self.control.is_some_and(SortControl::is_cancelled)
+    }
+
+    /// 比較が終わったことを記録する
+    fn advance(&self, comparisons: usize) {
+        if let Some(control) = self.control {
+            control.advance(comparisons);
+        }
+    }
+
+    /// 最初に起きたエラーを記録し、処理を中断させる
+    fn abort(&self, e: E) {
+        let mut error = self
+            .error
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if error.is_none() {
+            *error = Some(e);
+        }
+        self.aborted.store(true, Relaxed);
+    }
+
+    /// 記録されたエラーを取り出す
+    fn into_error(self) -> Option<E> {
+        self.error
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// 失敗する可能性のある比較で配列をソートする
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+/// - abort : 中断のための状態
+fn try_do_sort<T, E, F>(array: &mut [T], comparator: &F, forward: bool, abort: &Abort<E>)
+where
+    T: Send,
+    E: Send,
+    F: Sync + Fn(&T, &T) -> Result<Ordering, E>,
+{
+    if array.len() <= 1 || abort.is_aborted() {
+        return;
+    }
+
+    let mid = array.len() / 2;
+    let (first, second) = array.split_at_mut(mid);
+
+    if mid >= PARALLEL_THRESHOLD {
+        rayon::join(
+            || try_do_sort(first, comparator, true, abort),
+            || try_do_sort(second, comparator, false, abort),
+        );
+    } else {
+        try_do_sort(first, comparator, true, abort);
+        try_do_sort(second, comparator, false, abort);
+    }
+
+    try_sub_sort(array, comparator, forward, abort);
+}
+
+/// 失敗する可能性のある比較でバイトニック列をソートする
+/// # 引数
+/// - bitonic_array : バイトニック列
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+/// - abort : 中断のための状態
+fn try_sub_sort<T, E, F>(bitonic_array: &mut [T], comparator: &F, forward: bool, abort: &Abort<E>)
+where
+    T: Send,
+    E: Send,
+    F: Sync + Fn(&T, &T) -> Result<Ordering, E>,
+{
+    if bitonic_array.len() <= 1 || abort.is_aborted() {
+        return;
+    }
+    try_compare_and_swap(bitonic_array, comparator, forward, abort);
+
+    let mid = bitonic_array.len() / 2;
+    let (first, second) = bitonic_array.split_at_mut(mid);
+
+    if mid >= PARALLEL_THRESHOLD {
+        rayon::join(
+            || try_sub_sort(first, comparator, forward, abort),
+            || try_sub_sort(second, comparator, forward, abort),
+        );
+    } else {
+        try_sub_sort(first, comparator, forward, abort);
+        try_sub_sort(second, comparator, forward, abort);
+    }
+}
+
+/// 失敗する可能性のある比較で、各要素を要素数n / 2だけ右の要素と比較し並べ替える
+/// 比較がエラーを返した場合、エラーを記録してその場で中断する
+/// # 引数
+/// - array : 並び替え対象の配列
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+/// - abort : 中断のための状態
+fn try_compare_and_swap<T, E, F>(array: &mut [T], comparator: &F, forward: bool, abort: &Abort<E>)
+where
+    F: Fn(&T, &T) -> Result<Ordering, E>,
+{
+    let mid = array.len() / 2;
+    let expected = if forward { Greater } else { Less };
+
+    for i in 0..mid {
+        if abort.is_aborted() {
+            return;
+        }
+        match comparator(&array[i], &array[i + mid]) {
+            Ok(ordering) if ordering == expected => array.swap(i, i + mid),
+            Ok(_) => {}
+            Err(e) => {
+                abort.abort(e);
+                return;
+            }
+        }
+    }
+    abort.advance(mid);
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{sort, sort_by};
+    use super::{
+        apply_permutation, argsort_by, checked_sort_by, sort, sort_by, sort_by_cached_key,
+        sort_by_catch_unwind, sort_by_key, sort_by_with_control, sort_hybrid, sort_with_control,
+        try_sort_by,
+    };
+    use crate::checked::Inconsistency;
+    use crate::control::SortControl;
+    use crate::hybrid::BaseCase;
+    use crate::third;
     use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use crate::SortError;
     use crate::SortOrder::*;
+    use std::cmp::Ordering::*;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 
     #[derive(Debug, PartialEq)] //これがないとassert_eq!ができない
     struct Student {
@@ -193,6 +660,71 @@ mod tests {
         assert_eq!(array, expected);
     }
 
+    #[test]
+    fn sort_students_by_key_age_descending() {
+        let taro = Student::new("Taro", "Yamada", 16);
+        let hanako = Student::new("Hanako", "Yamada", 14);
+        let kyoko = Student::new("Kyoko", "Ito", 15);
+        let ryosuke = Student::new("Ryosuke", "Hayashi", 17);
+
+        let mut array = vec![&taro, &hanako, &kyoko, &ryosuke];
+
+        let expected = vec![&ryosuke, &taro, &kyoko, &hanako];
+
+        assert!(sort_by_key(&mut array, &|s| s.age, &Descending).is_ok());
+
+        assert_eq!(array, expected);
+    }
+
+    #[test]
+    fn sort_students_by_cached_key_name_ascending() {
+        let taro = Student::new("Taro", "Yamada", 16);
+        let hanako = Student::new("Hanako", "Yamada", 14);
+        let kyoko = Student::new("Kyoko", "Ito", 15);
+        let ryosuke = Student::new("Ryosuke", "Hayashi", 17);
+
+        let mut array = vec![&taro, &hanako, &kyoko, &ryosuke];
+
+        let expected = vec![&ryosuke, &kyoko, &hanako, &taro];
+
+        assert!(sort_by_cached_key(
+            &mut array,
+            &|s| format!("{} {}", s.last_name, s.first_name),
+            &Ascending
+        )
+        .is_ok());
+
+        assert_eq!(array, expected);
+    }
+
+    // キーは各要素につき1回だけ計算される
+    #[test]
+    fn sort_by_cached_key_computes_each_key_once() {
+        let mut x = new_u32_vec(1 << 10);
+        let calls = AtomicUsize::new(0);
+
+        let key = |v: &u32| {
+            calls.fetch_add(1, SeqCst);
+            v.count_ones()
+        };
+        assert_eq!(sort_by_cached_key(&mut x, &key, &Ascending), Ok(()));
+
+        assert_eq!(calls.load(SeqCst), 1 << 10);
+        assert!(x
+            .windows(2)
+            .all(|pair| pair[0].count_ones() <= pair[1].count_ones()));
+    }
+
+    // 要素が2のべき乗個ではない場合
+    #[test]
+    fn sort_by_cached_key_not_power_of_two() {
+        let mut array: Vec<u32> = vec![45, 21, 11];
+
+        assert!(sort_by_cached_key(&mut array, &|v| *v, &Ascending).is_err());
+
+        assert_eq!(array, vec![45, 21, 11]);
+    }
+
     // 昇順ソートのテスト
     #[test]
     fn sort_u32_ascending() {
@@ -338,4 +870,376 @@ mod tests {
         assert_eq!(sort(&mut x, &Descending), Ok(()));
         assert!(is_sorted_descending(&x));
     }
+
+    #[test]
+    fn sort_u32_large_hybrid() {
+        for base_case in &[BaseCase::Network, BaseCase::Insertion] {
+            let mut x = new_u32_vec(1 << 16);
+            let mut y = new_u32_vec(1 << 16);
+
+            assert_eq!(sort_hybrid(&mut x, &Ascending, base_case), Ok(()));
+            assert_eq!(sort_hybrid(&mut y, &Descending, base_case), Ok(()));
+
+            assert!(is_sorted_ascending(&x));
+            assert!(is_sorted_descending(&y));
+        }
+    }
+
+    // 基底ケースより小さい配列
+    #[test]
+    fn sort_u32_hybrid_smaller_than_cutoff() {
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+
+        assert!(sort_hybrid(&mut array, &Descending, &BaseCase::Network).is_ok());
+
+        assert_eq!(array, vec![330, 110, 30, 21, 20, 11, 10, 4]);
+    }
+
+    #[test]
+    fn try_sort_u32_ok() {
+        let mut x = new_u32_vec(1 << 16);
+
+        assert_eq!(
+            try_sort_by(&mut x, &|a: &u32, b: &u32| Ok::<_, ()>(b.cmp(a))),
+            Ok(())
+        );
+        assert!(is_sorted_descending(&x));
+    }
+
+    // 比較がエラーを返した場合、中断してエラーを返す。配列は入力を並べ替えたものになっている
+    #[test]
+    fn try_sort_u32_comparator_error() {
+        let input = new_u32_vec(1 << 16);
+        let mut x = input.clone();
+        let calls = AtomicUsize::new(0);
+        let comparator = |a: &u32, b: &u32| {
+            if calls.fetch_add(1, SeqCst) == 300_000 {
+                Err("failed")
+            } else {
+                Ok(a.cmp(b))
+            }
+        };
+
+        assert_eq!(
+            try_sort_by(&mut x, &comparator),
+            Err(SortError::Comparator("failed"))
+        );
+
+        let (mut actual, mut expected) = (x, input);
+        actual.sort();
+        expected.sort();
+        assert_eq!(actual, expected);
+    }
+
+    // 要素が2のべき乗個ではない場合
+    #[test]
+    fn try_sort_not_power_of_two() {
+        let mut array: Vec<u32> = vec![45, 21, 11];
+
+        assert_eq!(
+            try_sort_by(&mut array, &|a: &u32, b: &u32| Ok::<_, ()>(a.cmp(b))),
+            Err(SortError::NotPowerOfTwo(3))
+        );
+    }
+
+    /// 破棄された回数を数える要素
+    #[derive(Debug)]
+    struct Tracked<'a> {
+        value: u32,
+        drops: &'a AtomicUsize,
+    }
+
+    impl Drop for Tracked<'_> {
+        fn drop(&mut self) {
+            self.drops.fetch_add(1, SeqCst);
+        }
+    }
+
+    // 比較がパニックした後も、要素が重複したり失われたりしていない
+    #[test]
+    fn sort_by_comparator_panics() {
+        let input = new_u32_vec(1 << 16);
+        let drops = AtomicUsize::new(0);
+        let calls = AtomicUsize::new(0);
+        let mut x: Vec<Tracked> = input
+            .iter()
+            .map(|&value| Tracked {
+                value,
+                drops: &drops,
+            })
+            .collect();
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            sort_by(&mut x, &|a, b| {
+                if calls.fetch_add(1, SeqCst) == 300_000 {
+                    panic!("comparator panicked");
+                }
+                a.value.cmp(&b.value)
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(drops.load(SeqCst), 0);
+
+        let mut actual: Vec<u32> = x.iter().map(|t| t.value).collect();
+        let mut expected = input;
+        actual.sort();
+        expected.sort();
+        assert_eq!(actual, expected);
+
+        drop(x);
+        assert_eq!(drops.load(SeqCst), 1 << 16);
+    }
+
+    #[test]
+    fn sort_by_catch_unwind_comparator_panics() {
+        let input = new_u32_vec(1 << 16);
+        let mut x = input.clone();
+        let calls = AtomicUsize::new(0);
+
+        let result = sort_by_catch_unwind(&mut x, &|a: &u32, b: &u32| {
+            if calls.fetch_add(1, SeqCst) == 300_000 {
+                panic!("comparator panicked");
+            }
+            a.cmp(b)
+        });
+        assert_eq!(result, Err(SortError::ComparatorPanicked));
+
+        let (mut actual, mut expected) = (x, input);
+        actual.sort();
+        expected.sort();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn sort_by_catch_unwind_ok() {
+        let mut x = new_u32_vec(1 << 16);
+
+        assert_eq!(
+            sort_by_catch_unwind(&mut x, &|a: &u32, b: &u32| a.cmp(b)),
+            Ok(())
+        );
+        assert!(is_sorted_ascending(&x));
+
+        let mut array: Vec<u32> = vec![45, 21, 11];
+        assert_eq!(
+            sort_by_catch_unwind(&mut array, &|a: &u32, b: &u32| a.cmp(b)),
+            Err(SortError::NotPowerOfTwo(3))
+        );
+    }
+
+    #[test]
+    fn checked_sort_u32_ok() {
+        let mut x = new_u32_vec(1 << 14);
+
+        assert_eq!(
+            checked_sort_by(&mut x, &|a: &u32, b: &u32| a.cmp(b)),
+            Ok(())
+        );
+        assert!(is_sorted_ascending(&x));
+    }
+
+    // 反対称律を満たさない比較の場合
+    #[test]
+    fn checked_sort_not_antisymmetric() {
+        let input = new_u32_vec(1 << 14);
+        let mut x = input.clone();
+
+        let result = checked_sort_by(&mut x, &|a: &u32, b: &u32| {
+            if a.cmp(b) == Equal {
+                Equal
+            } else {
+                Less
+            }
+        });
+
+        assert!(matches!(
+            result,
+            Err(SortError::Comparator(Inconsistency::Antisymmetry { .. }))
+        ));
+        assert_eq!(x, input);
+    }
+
+    // 推移律を満たさない比較の場合
+    #[test]
+    fn checked_sort_not_transitive() {
+        let mut x = new_u32_vec(1 << 14);
+
+        // 値の差が小さいものを等しいとみなす
+        let result = checked_sort_by(&mut x, &|a: &u32, b: &u32| {
+            if (*a as i64 - *b as i64).abs() < 1 << 30 {
+                Equal
+            } else {
+                a.cmp(b)
+            }
+        });
+
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("not transitive") || message.contains("not sorted"));
+    }
+
+    // じゃんけんの比較（0 < 1 < 2 < 0）では、3つの異なる値を検査すると必ず推移律の違反になる
+    // 要素数が少なく逐次にソートされるので、比較の順序は毎回同じになる
+    #[test]
+    fn checked_sort_rock_paper_scissors() {
+        let input: Vec<u32> = (0..1 << 6).map(|i| i % 3).collect();
+        let mut x = input.clone();
+
+        let result = checked_sort_by(&mut x, &|a: &u32, b: &u32| {
+            if a == b {
+                Equal
+            } else if (a + 1) % 3 == *b {
+                Less
+            } else {
+                Greater
+            }
+        });
+
+        assert!(matches!(
+            result,
+            Err(SortError::Comparator(Inconsistency::Transitivity { .. }))
+        ));
+        assert_eq!(x, input);
+    }
+
+    #[test]
+    fn sort_with_control_completes() {
+        let mut x = new_u32_vec(1 << 16);
+        let control = SortControl::new();
+
+        assert_eq!(sort_with_control(&mut x, &Descending, &control), Ok(()));
+
+        assert!(is_sorted_descending(&x));
+        assert_eq!(control.percent(), 100.0);
+        assert_eq!(control.completed_layers(), 16 * 17 / 2);
+        assert_eq!(control.total_layers(), 16 * 17 / 2);
+    }
+
+    // ソートの途中で中断する
+    #[test]
+    fn sort_with_control_cancelled() {
+        let input = new_u32_vec(1 << 16);
+        let mut x = input.clone();
+        let control = SortControl::new();
+        let calls = AtomicUsize::new(0);
+
+        let result = sort_by_with_control(
+            &mut x,
+            &|a: &u32, b: &u32| {
+                if calls.fetch_add(1, SeqCst) == 300_000 {
+                    control.cancel();
+                }
+                a.cmp(b)
+            },
+            &control,
+        );
+        assert_eq!(result, Err(SortError::Cancelled));
+        assert!(control.percent() > 0.0 && control.percent() < 100.0);
+
+        let (mut actual, mut expected) = (x, input);
+        actual.sort();
+        expected.sort();
+        assert_eq!(actual, expected);
+
+        // 中断したハンドルを使うと、次のソートもすぐに中断する
+        let mut x = new_u32_vec(1 << 4);
+        assert_eq!(
+            sort_with_control(&mut x, &Ascending, &control),
+            Err(SortError::Cancelled)
+        );
+        assert_eq!(control.percent(), 0.0);
+    }
+
+    // 最後の比較の中で中断が要求された場合は、ソートは終わっている
+    #[test]
+    fn sort_with_control_cancelled_after_last_comparison() {
+        let mut x = vec![2u32, 1];
+        let control = SortControl::new();
+
+        let result = sort_by_with_control(
+            &mut x,
+            &|a: &u32, b: &u32| {
+                control.cancel();
+                a.cmp(b)
+            },
+            &control,
+        );
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(x, vec![1, 2]);
+        assert_eq!(control.percent(), 100.0);
+    }
+
+    #[test]
+    fn argsort_u32_same_as_third() {
+        let keys: Vec<u32> = new_u32_vec(1 << 16).into_iter().map(|x| x % 1000).collect();
+
+        let perm = argsort_by(&keys, &|a: &u32, b: &u32| a.cmp(b)).unwrap();
+
+        // 添字で同順位を決めて、逐次版のソートと比べる
+        let mut expected: Vec<(u32, usize)> = keys.iter().copied().zip(0..).collect();
+        assert!(third::sort(&mut expected, &Ascending).is_ok());
+        let expected: Vec<usize> = expected.into_iter().map(|(_, i)| i).collect();
+        assert_eq!(perm, expected);
+    }
+
+    #[test]
+    fn argsort_not_power_of_two() {
+        assert!(argsort_by(&[3u32, 1, 2], &|a: &u32, b: &u32| a.cmp(b)).is_err());
+    }
+
+    // 型の異なる複数の列を、キーの列の順に並べ替える
+    #[test]
+    fn apply_permutation_to_columns() {
+        let ids = new_u32_vec(1 << 14);
+        let mut names: Vec<String> = ids.iter().map(|id| format!("name{}", id)).collect();
+        let mut scores: Vec<u64> = ids.iter().map(|&id| u64::from(id) * 3).collect();
+        let mut rows: Vec<(u32, String, u64)> = ids
+            .iter()
+            .zip(&names)
+            .zip(&scores)
+            .map(|((&id, name), &score)| (id, name.clone(), score))
+            .collect();
+        let mut ids = ids;
+
+        let perm = argsort_by(&ids, &|a: &u32, b: &u32| b.cmp(a)).unwrap();
+        let result = apply_permutation(&mut [&mut ids, &mut names, &mut scores], &perm);
+        assert_eq!(result, Ok(()));
+
+        assert!(third::sort_by(&mut rows, &|a, b| b.0.cmp(&a.0)).is_ok());
+        assert_eq!(ids, rows.iter().map(|row| row.0).collect::<Vec<_>>());
+        assert_eq!(
+            names,
+            rows.iter().map(|row| row.1.clone()).collect::<Vec<_>>()
+        );
+        assert_eq!(scores, rows.iter().map(|row| row.2).collect::<Vec<_>>());
+    }
+
+    // 複製できない要素の列も並べ替えられる
+    #[test]
+    fn apply_permutation_to_non_clone_column() {
+        #[derive(Debug, PartialEq)]
+        struct Token(u32);
+
+        let mut tokens: Vec<Token> = (0..6).map(Token).collect();
+        let perm = [3, 5, 0, 1, 4, 2];
+
+        assert_eq!(apply_permutation(&mut [&mut tokens], &perm), Ok(()));
+
+        let expected: Vec<Token> = perm.iter().map(|&i| Token(i as u32)).collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn apply_permutation_invalid() {
+        let mut x = vec![10u32, 20, 30];
+        let mut y = vec!['a', 'b'];
+
+        assert!(apply_permutation(&mut [&mut x, &mut y], &[1, 0, 2]).is_err());
+        assert!(apply_permutation(&mut [&mut x], &[1, 1, 2]).is_err());
+        assert!(apply_permutation(&mut [&mut x], &[1, 3, 2]).is_err());
+        assert_eq!(x, vec![10, 20, 30]);
+
+        assert_eq!(apply_permutation(&mut [&mut x], &[2, 0, 1]), Ok(()));
+        assert_eq!(x, vec![30, 10, 20]);
+    }
 }
@@ -0,0 +1,241 @@
+use crate::network::{merge_array_by, sort_array_by};
+use std::cmp::Ordering;
+use std::cmp::Ordering::*;
+use std::convert::TryFrom;
+
+/// 展開済みのソーティングネットワークに切り替える要素数
+pub const NETWORK_CUTOFF: usize = 16;
+
+/// 挿入ソートに切り替える要素数
+pub const INSERTION_CUTOFF: usize = 32;
+
+/// 小さな部分配列のソート方法
+pub enum BaseCase {
+    /// 要素数が1になるまで再帰する
+    Recursive,
+    /// 要素数がNETWORK_CUTOFF以下になったら展開済みのソーティングネットワークを使う
+    Network,
+    /// 要素数がINSERTION_CUTOFF以下になったら挿入ソートを使う
+    /// 比較の順序がデータに依存するため、データ非依存（oblivious）ではなくなる
+    Insertion,
+}
+
+/// 要素数が基底ケースの範囲内であれば配列をソートする
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+/// - base_case : 小さな部分配列のソート方法
+/// # 戻り値
+/// - ソートした場合true、要素数が範囲外で何もしなかった場合false
+pub(crate) fn sort_small<T, F>(
+    array: &mut [T],
+    comparator: &F,
+    forward: bool,
+    base_case: &BaseCase,
+) -> bool
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    match *base_case {
+        BaseCase::Recursive => false,
+        BaseCase::Network if array.len() <= NETWORK_CUTOFF => {
+            if forward {
+                sort_network(array, comparator);
+            } else {
+                sort_network(array, &|a: &T, b: &T| comparator(b, a));
+            }
+            true
+        }
+        BaseCase::Insertion if array.len() <= INSERTION_CUTOFF => {
+            insertion_sort(array, comparator, forward);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// 要素数が基底ケースの範囲内であればバイトニック列をソートする
+/// # 引数
+/// - bitonic_array : バイトニック列。ただし、要素数は2^nでなければならない
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+/// - base_case : 小さな部分配列のソート方法
+/// # 戻り値
+/// - ソートした場合true、要素数が範囲外で何もしなかった場合false
+pub(crate) fn merge_small<T, F>(
+    bitonic_array: &mut [T],
+    comparator: &F,
+    forward: bool,
+    base_case: &BaseCase,
+) -> bool
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    match *base_case {
+        BaseCase::Network if bitonic_array.len() <= NETWORK_CUTOFF => {
+            if forward {
+                merge_network(bitonic_array, comparator);
+            } else {
+                merge_network(bitonic_array, &|a: &T, b: &T| comparator(b, a));
+            }
+            true
+        }
+        _ => sort_small(bitonic_array, comparator, forward, base_case),
+    }
+}
+
+/// 要素数に応じた展開済みのソーティングネットワークでソートする
+/// # 引数
+/// - array : 配列。ただし、要素数はNETWORK_CUTOFF以下の2^nでなければならない
+/// - comparator : 比較のためのクロージャ
+fn sort_network<T, F>(array: &mut [T], comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    match array.len() {
+        16 => sort_array_by(<&mut [T; 16]>::try_from(array).unwrap(), comparator),
+        8 => sort_array_by(<&mut [T; 8]>::try_from(array).unwrap(), comparator),
+        4 => sort_array_by(<&mut [T; 4]>::try_from(array).unwrap(), comparator),
+        2 => sort_array_by(<&mut [T; 2]>::try_from(array).unwrap(), comparator),
+        _ => {}
+    }
+}
+
+/// 要素数に応じた展開済みのマージネットワークでバイトニック列をソートする
+/// # 引数
+/// - bitonic_array : バイトニック列。ただし、要素数はNETWORK_CUTOFF以下の2^nでなければならない
+/// - comparator : 比較のためのクロージャ
+fn merge_network<T, F>(bitonic_array: &mut [T], comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    match bitonic_array.len() {
+        16 => merge_array_by(<&mut [T; 16]>::try_from(bitonic_array).unwrap(), comparator),
+        8 => merge_array_by(<&mut [T; 8]>::try_from(bitonic_array).unwrap(), comparator),
+        4 => merge_array_by(<&mut [T; 4]>::try_from(bitonic_array).unwrap(), comparator),
+        2 => merge_array_by(<&mut [T; 2]>::try_from(bitonic_array).unwrap(), comparator),
+        _ => {}
+    }
+}
+
+/// 挿入ソートでソートする
+/// # 引数
+/// - array : 配列
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+fn insertion_sort<T, F>(array: &mut [T], comparator: &F, forward: bool)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let expected = if forward { Greater } else { Less };
+    for i in 1..array.len() {
+        let mut j = i;
+        while j > 0 && comparator(&array[j - 1], &array[j]) == expected {
+            array.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_small, sort_small, BaseCase};
+    use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+
+    #[test]
+    fn sort_small_network() {
+        for &len in &[2, 4, 8, 16] {
+            let mut x = new_u32_vec(len);
+            let mut y = new_u32_vec(len);
+
+            assert!(sort_small(
+                &mut x,
+                &|a, b| a.cmp(b),
+                true,
+                &BaseCase::Network
+            ));
+            assert!(sort_small(
+                &mut y,
+                &|a, b| a.cmp(b),
+                false,
+                &BaseCase::Network
+            ));
+
+            assert!(is_sorted_ascending(&x));
+            assert!(is_sorted_descending(&y));
+        }
+    }
+
+    #[test]
+    fn sort_small_insertion() {
+        let mut x = new_u32_vec(32);
+        let mut y = new_u32_vec(32);
+
+        assert!(sort_small(
+            &mut x,
+            &|a, b| a.cmp(b),
+            true,
+            &BaseCase::Insertion
+        ));
+        assert!(sort_small(
+            &mut y,
+            &|a, b| a.cmp(b),
+            false,
+            &BaseCase::Insertion
+        ));
+
+        assert!(is_sorted_ascending(&x));
+        assert!(is_sorted_descending(&y));
+    }
+
+    #[test]
+    fn merge_small_network() {
+        let mut x: Vec<u32> = vec![4, 11, 30, 330, 110, 21, 20, 10];
+        let mut y = x.clone();
+
+        assert!(merge_small(
+            &mut x,
+            &|a, b| a.cmp(b),
+            true,
+            &BaseCase::Network
+        ));
+        assert!(merge_small(
+            &mut y,
+            &|a, b| a.cmp(b),
+            false,
+            &BaseCase::Network
+        ));
+
+        assert_eq!(x, vec![4, 10, 11, 20, 21, 30, 110, 330]);
+        assert_eq!(y, vec![330, 110, 30, 21, 20, 11, 10, 4]);
+    }
+
+    // 要素数が基底ケースの範囲外の場合
+    #[test]
+    fn sort_small_out_of_range() {
+        let mut x = new_u32_vec(64);
+        let expected = x.clone();
+
+        assert!(!sort_small(
+            &mut x,
+            &|a, b| a.cmp(b),
+            true,
+            &BaseCase::Recursive
+        ));
+        assert!(!sort_small(
+            &mut x,
+            &|a, b| a.cmp(b),
+            true,
+            &BaseCase::Network
+        ));
+        assert!(!sort_small(
+            &mut x,
+            &|a, b| a.cmp(b),
+            true,
+            &BaseCase::Insertion
+        ));
+
+        assert_eq!(x, expected);
+    }
+}
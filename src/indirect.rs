@@ -0,0 +1,146 @@
+use crate::fourth;
+use crate::utils::apply_indices;
+use crate::SortOrder;
+use crate::SortOrder::*;
+use std::cmp::Ordering;
+use std::mem;
+
+/// 要素の大きさ（バイト数）がこれより大きい場合、sort_byは添字をソートしてから要素を並べ替える
+pub const SIZE_THRESHOLD: usize = 128;
+
+/// 配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// 要素が大きい場合は、要素そのものではなく添字をソートする
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。また、要素はOrdを実装しなければならない。
+/// - order : ソート順
+pub fn sort<T: Ord + Send + Sync>(array: &mut [T], order: &SortOrder) -> Result<(), String> {
+    match *order {
+        Ascending => sort_by(array, &|a, b| a.cmp(b)),
+        Descending => sort_by(array, &|a, b| b.cmp(a)),
+    }
+}
+
+/// 配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// 要素の大きさがSIZE_THRESHOLDより大きい場合はsort_indirect_byを、それ以外の場合はfourth::sort_byを使う
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ
+pub fn sort_by<T, F>(array: &mut [T], comparator: &F) -> Result<(), String>
+where
+    T: Send + Sync,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    if mem::size_of::<T>() > SIZE_THRESHOLD {
+        sort_indirect_by(array, comparator)
+    } else {
+        fourth::sort_by(array, comparator)
+    }
+}
+
+/// 添字の配列をソートしてから、元の配列を1回だけ並べ替える。配列の要素が2^nではない場合、エラーを返す
+/// ソート中は添字だけを入れ替えるので、大きな要素を何度も移動せずに済む
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nかつ2^31以下でなければならない。
+/// - comparator : 大小比較するためのクロージャ
+pub fn sort_indirect_by<T, F>(array: &mut [T], comparator: &F) -> Result<(), String>
+where
+    T: Sync,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    if array.len() > u32::MAX as usize {
+        return Err(format!(
+            "The number of elements is too large for u32 indices. (len: {})",
+            array.len()
+        ));
+    }
+
+    let mut indices: Vec<u32> = (0..array.len()).map(|i| i as u32).collect();
+    {
+        let array = &*array;
+        fourth::sort_by(&mut indices, &|&a, &b| {
+            comparator(&array[a as usize], &array[b as usize])
+        })?;
+    }
+    apply_indices(array, &mut indices);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort, sort_by, sort_indirect_by, SIZE_THRESHOLD};
+    use crate::utils::{apply_indices, is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use crate::SortOrder::*;
+    use crate::{fourth, third};
+    use std::mem;
+
+    /// SIZE_THRESHOLDより大きい要素
+    #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+    struct Large {
+        key: u32,
+        payload: [u64; 32],
+    }
+
+    impl Large {
+        fn new(key: u32) -> Self {
+            Self {
+                key,
+                payload: [u64::from(key); 32],
+            }
+        }
+    }
+
+    #[test]
+    fn apply_u32_indices() {
+        let mut array = vec!['a', 'b', 'c', 'd', 'e'];
+        let mut indices = vec![3u32, 0, 4, 1, 2];
+
+        apply_indices(&mut array, &mut indices);
+
+        assert_eq!(array, vec!['d', 'a', 'e', 'b', 'c']);
+    }
+
+    #[test]
+    fn sort_large_ascending() {
+        assert!(mem::size_of::<Large>() > SIZE_THRESHOLD);
+        let mut x: Vec<Large> = new_u32_vec(1 << 12).into_iter().map(Large::new).collect();
+
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+
+        assert!(is_sorted_ascending(&x));
+        assert!(x.iter().all(|e| e.payload[31] == u64::from(e.key)));
+    }
+
+    #[test]
+    fn sort_large_descending_same_as_third() {
+        let mut x: Vec<Large> = new_u32_vec(1 << 12).into_iter().map(Large::new).collect();
+        let mut expected = x.clone();
+
+        assert_eq!(sort_by(&mut x, &|a: &Large, b: &Large| b.cmp(a)), Ok(()));
+        assert!(third::sort(&mut expected, &Descending).is_ok());
+
+        assert!(is_sorted_descending(&x));
+        assert_eq!(x, expected);
+    }
+
+    // 小さな要素でも、sort_indirect_byは直接ソートした場合と同じ結果になる
+    #[test]
+    fn sort_indirect_u32_same_as_fourth() {
+        let mut x = new_u32_vec(1 << 16);
+        let mut expected = x.clone();
+
+        assert_eq!(
+            sort_indirect_by(&mut x, &|a: &u32, b: &u32| a.cmp(b)),
+            Ok(())
+        );
+        assert!(fourth::sort(&mut expected, &Ascending).is_ok());
+
+        assert_eq!(x, expected);
+    }
+
+    #[test]
+    fn sort_elemtns_not_power_of_two() {
+        let mut x: Vec<Large> = vec![Large::new(3), Large::new(1), Large::new(2)];
+        assert!(sort(&mut x, &Ascending).is_err());
+        assert_eq!(x[0].key, 3);
+    }
+}
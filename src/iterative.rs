@@ -0,0 +1,140 @@
+use crate::network;
+use crate::SortOrder;
+use crate::SortOrder::*;
+use std::cmp::Ordering;
+
+/// 配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// 再帰を使わず、ループだけで比較器の順序をたどる
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。また、要素はOrdを実装しなければならない。
+/// - order : ソート順
+pub fn sort<T: Ord>(array: &mut [T], order: &SortOrder) -> Result<(), String> {
+    match *order {
+        Ascending => sort_by(array, &|a, b| a.cmp(b)),
+        Descending => sort_by(array, &|a, b| b.cmp(a)),
+    }
+}
+
+/// 配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// 再帰を使わず、ループだけで比較器の順序をたどる
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。また、要素はOrdを実装しなければならない。
+/// - comparator : 大小比較するためのクロージャ
+/// # 戻り値
+/// - 要素数が2^nの場合Okを、それ以外の場合Errを返す
+pub fn sort_by<T, F>(array: &mut [T], comparator: &F) -> Result<(), String>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    match array.len() {
+        0 => Ok(()),
+        n => {
+            if n.is_power_of_two() {
+                network::sort_slice_by(array, comparator);
+                Ok(())
+            } else {
+                Err(format!(
+                    "The length of array is not a power of two. (array.len(): {})",
+                    array.len()
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort, sort_by};
+    use crate::third;
+    use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use crate::SortOrder::*;
+    use rand::{Rng, SeedableRng};
+    use rand_pcg::Pcg64Mcg;
+
+    // 昇順ソートのテスト
+    #[test]
+    fn sort_u32_ascending() {
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+
+        assert!(sort(&mut array, &Ascending).is_ok());
+
+        assert_eq!(array, vec![4, 10, 11, 20, 21, 30, 110, 330]);
+    }
+
+    //　降順ソートのテスト
+    #[test]
+    fn sort_u32_descending() {
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+
+        assert!(sort(&mut array, &Descending).is_ok());
+
+        assert_eq!(array, vec![330, 110, 30, 21, 20, 11, 10, 4]);
+    }
+
+    // 要素が0個
+    #[test]
+    fn sort_zero() {
+        let mut array: Vec<u32> = vec![];
+
+        assert!(sort(&mut array, &Ascending).is_ok());
+
+        assert_eq!(array, vec![]);
+    }
+
+    // 要素が1個
+    #[test]
+    fn sort_one() {
+        let mut array: Vec<u32> = vec![45];
+
+        assert!(sort(&mut array, &Descending).is_ok());
+
+        assert_eq!(array, vec![45]);
+    }
+
+    #[test]
+    fn sort_str_by_length() {
+        let mut array = vec!["Rust", "is", "fast", "and"];
+
+        assert!(sort_by(&mut array, &|a, b| a.len().cmp(&b.len()).then(a.cmp(b))).is_ok());
+
+        assert_eq!(array, vec!["is", "and", "Rust", "fast"]);
+    }
+
+    // 要素が2のべき乗個ではない場合
+    #[test]
+    fn sort_elemtns_not_power_of_two() {
+        let mut array: Vec<u32> = vec![45, 21, 11];
+
+        assert!(sort(&mut array, &Descending).is_err());
+    }
+
+    #[test]
+    fn sort_u32_large() {
+        let mut x = new_u32_vec(1 << 16);
+        let mut y = x.clone();
+
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        assert_eq!(sort(&mut y, &Descending), Ok(()));
+
+        assert!(is_sorted_ascending(&x));
+        assert!(is_sorted_descending(&y));
+    }
+
+    // ランダムな入力でthirdと同じ結果になる
+    #[test]
+    fn same_as_third() {
+        let mut rng = Pcg64Mcg::from_seed([1; 16]);
+        for bits in 0..12 {
+            // 値の範囲を狭めて重複を含める
+            let x: Vec<(u8, u32)> = (0..1 << bits).map(|i| (rng.gen_range(0, 16), i)).collect();
+            let comparator = |a: &(u8, u32), b: &(u8, u32)| a.0.cmp(&b.0);
+
+            let mut expected = x.clone();
+            let mut actual = x;
+            assert!(third::sort_by(&mut expected, &comparator).is_ok());
+            assert!(sort_by(&mut actual, &comparator).is_ok());
+
+            assert_eq!(actual, expected);
+        }
+    }
+}
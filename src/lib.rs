@@ -1,10 +1,64 @@
+use std::convert::Infallible;
+use std::fmt;
+
+pub mod background;
+pub mod batch;
+pub mod blocked;
+pub mod branchless;
+pub mod checked;
+pub mod compare;
+pub mod control;
+pub mod external;
 pub mod first;
 pub mod fourth;
+pub mod hybrid;
+pub mod indirect;
+pub mod iterative;
+pub mod merge;
+pub mod mmap;
+pub mod network;
+pub mod normalized;
+pub mod plan;
+pub mod prefix;
+pub mod records;
 pub mod second;
+pub mod segment;
+pub mod spec;
 pub mod third;
 pub mod utils;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SortOrder {
     Ascending,
     Descending,
 }
+
+/// ソートに失敗した理由
+#[derive(Debug, PartialEq)]
+pub enum SortError<E = Infallible> {
+    /// 配列の要素数が2のべき乗ではない
+    NotPowerOfTwo(usize),
+    /// 比較のためのクロージャがエラーを返した
+    Comparator(E),
+    /// 比較のためのクロージャがパニックした
+    ComparatorPanicked,
+    /// SortControlによってソートが中断された
+    Cancelled,
+}
+
+impl<E: fmt::Display> fmt::Display for SortError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortError::NotPowerOfTwo(len) => write!(
+                f,
+                "The length of array is not a power of two. (array.len(): {})",
+                len
+            ),
+            SortError::Comparator(e) => write!(f, "The comparator failed: {}", e),
+            SortError::ComparatorPanicked => write!(f, "The comparator panicked."),
+            SortError::Cancelled => write!(f, "The sort was cancelled."),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for SortError<E> {}
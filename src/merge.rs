@@ -0,0 +1,311 @@
+use crate::SortOrder;
+use crate::SortOrder::*;
+use std::cmp::Ordering;
+use std::cmp::Ordering::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// 複数のソート済みの列の先頭から、最小の要素を繰り返し取り出すための敗者木
+/// 葉は各列の先頭の要素で、内部節点には対戦の敗者の列の番号を持つ
+/// 要素を取り出すたびに、勝者の葉から根までの対戦だけをやり直す
+pub(crate) struct LoserTree<T, F> {
+    /// 各列の先頭の要素。列が終わった場合はNone
+    heads: Vec<Option<T>>,
+    /// tree[0]は勝者、tree[1..]は各節点の敗者の列の番号。節点nの子は2nと2n + 1、列iの葉はk + i
+    tree: Vec<usize>,
+    comparator: F,
+}
+
+impl<T, F> LoserTree<T, F>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    /// 敗者木を作る
+    /// # 引数
+    /// - heads : 各列の先頭の要素。空の列はNoneとする
+    /// - comparator : 大小比較するためのクロージャ
+    pub(crate) fn new(heads: Vec<Option<T>>, comparator: F) -> Self {
+        let mut tree = Self {
+            tree: vec![0; heads.len().max(1)],
+            heads,
+            comparator,
+        };
+        if !tree.heads.is_empty() {
+            tree.tree[0] = tree.build(1);
+        }
+        tree
+    }
+
+    /// 節点nの部分木の対戦を行い、勝者を返す
+    fn build(&mut self, n: usize) -> usize {
+        let k = self.heads.len();
+        if n >= k {
+            return n - k;
+        }
+        let (a, b) = (self.build(2 * n), self.build(2 * n + 1));
+        let (winner, loser) = if self.beats(b, a) { (b, a) } else { (a, b) };
+        self.tree[n] = loser;
+        winner
+    }
+
+    /// 列aの先頭が列bの先頭より先に来るかどうかを返す
+    /// 終わった列は常に負け、等しい場合は番号の小さい列が勝つ
+    fn beats(&self, a: usize, b: usize) -> bool {
+        match (&self.heads[a], &self.heads[b]) {
+            (Some(x), Some(y)) => match (self.comparator)(x, y) {
+                Less => true,
+                Equal => a < b,
+                Greater => false,
+            },
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    /// 最小の要素とその列の番号を返す。すべての列が終わった場合はNone
+    pub(crate) fn peek(&self) -> Option<(usize, &T)> {
+        let winner = *self.tree.first()?;
+        self.heads.get(winner)?.as_ref().map(|head| (winner, head))
+    }
+
+    /// 勝者の列の先頭を次の要素に置き換え、元の先頭を返す
+    /// # 引数
+    /// - next : 勝者の列の次の要素。列が終わった場合はNone
+    pub(crate) fn replace(&mut self, next: Option<T>) -> Option<T> {
+        let k = self.heads.len();
+        let mut winner = *self.tree.first()?;
+        let head = std::mem::replace(self.heads.get_mut(winner)?, next);
+
+        let mut n = (winner + k) / 2;
+        while n >= 1 {
+            if self.beats(self.tree[n], winner) {
+                std::mem::swap(&mut self.tree[n], &mut winner);
+            }
+            n /= 2;
+        }
+        self.tree[0] = winner;
+        head
+    }
+}
+
+/// ソート済みのテキストファイルを併合し、1つのソート済みの行の並びとして書き出す
+/// 行はバイト列の辞書順で比較する
+/// # 引数
+/// - inputs : 入力ファイルのパス。各ファイルの行はorderにしたがってソートされていなければならない
+/// - output : 出力先
+/// - order : ソート順
+/// # 戻り値
+/// - 書き出した行の数。ソートされていない入力がある場合Err
+pub fn merge_files<P, W>(inputs: &[P], output: &mut W, order: &SortOrder) -> Result<u64, String>
+where
+    P: AsRef<Path>,
+    W: Write,
+{
+    match *order {
+        Ascending => merge_files_by(inputs, output, &|a: &String, b: &String| a.cmp(b)),
+        Descending => merge_files_by(inputs, output, &|a: &String, b: &String| b.cmp(a)),
+    }
+}
+
+/// ソート済みのテキストファイルを併合し、1つのソート済みの行の並びとして書き出す
+/// 各ファイルを1行ずつ読むので、メモリに収まらない大きさのファイルも併合できる
+/// # 引数
+/// - inputs : 入力ファイルのパス。各ファイルの行はcomparatorにしたがってソートされていなければならない
+/// - output : 出力先
+/// - comparator : 大小比較するためのクロージャ
+/// # 戻り値
+/// - 書き出した行の数。ソートされていない入力がある場合Err
+pub fn merge_files_by<P, W, F>(inputs: &[P], output: &mut W, comparator: &F) -> Result<u64, String>
+where
+    P: AsRef<Path>,
+    W: Write,
+    F: Fn(&String, &String) -> Ordering,
+{
+    let mut readers = inputs
+        .iter()
+        .map(|path| Lines::open(path.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let heads = readers
+        .iter_mut()
+        .map(Lines::next_line)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut tree = LoserTree::new(heads, comparator);
+    let mut written = 0;
+    while let Some((i, _)) = tree.peek() {
+        let next = readers[i].next_line()?;
+        // 同じファイルの次の行が、今の行より前に来る場合はソートされていない
+        if let (Some(next), Some((_, line))) = (&next, tree.peek()) {
+            if comparator(next, line) == Less {
+                return Err(format!(
+                    "{}: The file is not sorted. (line: {})",
+                    readers[i].path, readers[i].line_number
+                ));
+            }
+        }
+
+        if let Some(line) = tree.replace(next) {
+            writeln!(output, "{}", line).map_err(|e| e.to_string())?;
+            written += 1;
+        }
+    }
+    output.flush().map_err(|e| e.to_string())?;
+    Ok(written)
+}
+
+/// ファイルを1行ずつ読む
+struct Lines {
+    path: String,
+    reader: BufReader<File>,
+    /// 読んだ行の数
+    line_number: u64,
+}
+
+impl Lines {
+    fn open(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Self {
+            path: path.display().to_string(),
+            reader: BufReader::new(file),
+            line_number: 0,
+        })
+    }
+
+    fn next_line(&mut self) -> Result<Option<String>, String> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Ok(None),
+            Ok(_) => {
+                if line.ends_with('\n') {
+                    line.pop();
+                }
+                self.line_number += 1;
+                Ok(Some(line))
+            }
+            Err(e) => Err(format!("{}: {}", self.path, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_files, merge_files_by, LoserTree};
+    use crate::compare::strings;
+    use crate::utils::new_u32_vec;
+    use crate::SortOrder::*;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    /// テストごとに異なる一時ディレクトリを作る
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("bitonic-sorter-{}-merge-{}", process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn loser_tree_merges_sorted_lists() {
+        // 列の数が2^nではない場合や、空の列を含む場合
+        for k in 1..10 {
+            let mut lists: Vec<Vec<u32>> = (0..k)
+                .map(|i| {
+                    let mut list = new_u32_vec(i * 37 % 50);
+                    list.sort();
+                    list
+                })
+                .collect();
+            let mut expected: Vec<u32> = lists.concat();
+            expected.sort();
+
+            let heads = lists
+                .iter_mut()
+                .map(|list| {
+                    list.reverse();
+                    list.pop()
+                })
+                .collect();
+            let mut tree = LoserTree::new(heads, |a: &u32, b: &u32| a.cmp(b));
+            let mut actual = Vec::new();
+            while let Some((i, _)) = tree.peek() {
+                let next = lists[i].pop();
+                actual.extend(tree.replace(next));
+            }
+
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn loser_tree_no_lists() {
+        let mut tree = LoserTree::new(Vec::<Option<u32>>::new(), |a: &u32, b: &u32| a.cmp(b));
+        assert!(tree.peek().is_none());
+        assert!(tree.replace(None).is_none());
+    }
+
+    #[test]
+    fn merge_files_descending() {
+        let dir = temp_dir("descending");
+        let paths = [dir.join("a"), dir.join("b"), dir.join("c")];
+        fs::write(&paths[0], "pear\nlemon\napple\n").unwrap();
+        fs::write(&paths[1], "").unwrap();
+        fs::write(&paths[2], "orange\nfig").unwrap();
+
+        let mut output = Vec::new();
+        let result = merge_files(&paths, &mut output, &Descending);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result, Ok(5));
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "pear\norange\nlemon\nfig\napple\n"
+        );
+    }
+
+    #[test]
+    fn merge_files_naturally() {
+        let dir = temp_dir("natural");
+        let paths = [dir.join("a"), dir.join("b")];
+        fs::write(&paths[0], "file1\nfile10\n").unwrap();
+        fs::write(&paths[1], "file2\nfile9\nfile11\n").unwrap();
+
+        let mut output = Vec::new();
+        let result = merge_files_by(&paths, &mut output, &strings::natural);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result, Ok(5));
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "file1\nfile2\nfile9\nfile10\nfile11\n"
+        );
+    }
+
+    // ソートされていない入力
+    #[test]
+    fn merge_files_not_sorted() {
+        let dir = temp_dir("unsorted");
+        let paths = [dir.join("a"), dir.join("b")];
+        fs::write(&paths[0], "a\nc\n").unwrap();
+        fs::write(&paths[1], "b\nd\nc\n").unwrap();
+
+        let mut output = Vec::new();
+        let result = merge_files(&paths, &mut output, &Ascending);
+
+        fs::remove_dir_all(&dir).unwrap();
+        let err = result.unwrap_err();
+        assert!(err.contains("(line: 3)"));
+        assert!(err.contains(&paths[1].display().to_string()));
+    }
+
+    #[test]
+    fn merge_missing_file() {
+        let dir = temp_dir("missing");
+        let mut output = Vec::new();
+        let result = merge_files(&[dir.join("missing")], &mut output, &Ascending);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_err());
+    }
+}
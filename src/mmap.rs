@@ -0,0 +1,214 @@
+use crate::fourth;
+use crate::segment;
+use crate::SortOrder;
+use memmap2::MmapMut;
+use rayon::prelude::*;
+use std::fs::OpenOptions;
+use std::mem;
+use std::path::Path;
+
+/// ファイルに書かれた整数のバイト順
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    fn is_native(&self) -> bool {
+        match *self {
+            Endian::Little => cfg!(target_endian = "little"),
+            Endian::Big => cfg!(target_endian = "big"),
+        }
+    }
+}
+
+/// ファイルの要素の型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElemType {
+    U32(Endian),
+    U64(Endian),
+}
+
+/// 符号なし整数の配列としてファイルをメモリにマップし、その場でソートする
+/// 要素数が2^nの場合はfourth::sortで、それ以外の場合は任意の要素数を扱えるsegmentのソートでソートする
+/// ソートが終わったら、変更をファイルに書き出してから戻る
+/// # 引数
+/// - path : ファイルのパス。読み書きできなければならない
+/// - elem_type : 要素の型とバイト順
+/// - order : ソート順
+/// # 戻り値
+/// - ファイルを開けない場合や、ファイルの長さが要素の大きさの倍数ではない場合Errを返す
+pub fn sort_file_in_place<P: AsRef<Path>>(
+    path: P,
+    elem_type: ElemType,
+    order: &SortOrder,
+) -> Result<(), String> {
+    let path = path.as_ref();
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let len = file
+        .metadata()
+        .map_err(|e| format!("{}: {}", path.display(), e))?
+        .len();
+    if len == 0 {
+        return Ok(());
+    }
+
+    // 他のプロセスがファイルを同時に変更しないことを前提とする
+    let mut map =
+        unsafe { MmapMut::map_mut(&file) }.map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    match elem_type {
+        ElemType::U32(endian) => sort_words::<u32>(&mut map, endian, order)?,
+        ElemType::U64(endian) => sort_words::<u64>(&mut map, endian, order)?,
+    }
+
+    map.flush()
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// バイト順を入れ替えられる符号なし整数
+trait Word: Ord + Send + Copy {
+    fn swap_bytes(self) -> Self;
+}
+
+impl Word for u32 {
+    fn swap_bytes(self) -> Self {
+        u32::swap_bytes(self)
+    }
+}
+
+impl Word for u64 {
+    fn swap_bytes(self) -> Self {
+        u64::swap_bytes(self)
+    }
+}
+
+/// バイト列を整数の配列とみなしてソートする
+/// バイト順がCPUと異なる場合は、ソートの前後でバイト順を入れ替える
+fn sort_words<T: Word>(bytes: &mut [u8], endian: Endian, order: &SortOrder) -> Result<(), String> {
+    if !bytes.len().is_multiple_of(mem::size_of::<T>()) {
+        return Err(format!(
+            "The file length is not a multiple of the element size. (length: {}, element size: {})",
+            bytes.len(),
+            mem::size_of::<T>()
+        ));
+    }
+
+    // マップした領域はページ境界から始まるので、整数の境界にも揃っている
+    // また、どのビットの並びも有効なu32やu64になる
+    let (head, words, tail) = unsafe { bytes.align_to_mut::<T>() };
+    if !head.is_empty() || !tail.is_empty() {
+        return Err("The mapped file is not aligned to the element size.".to_string());
+    }
+
+    let swap = !endian.is_native();
+    if swap {
+        words.par_iter_mut().for_each(|w| *w = w.swap_bytes());
+    }
+
+    if words.len().is_power_of_two() {
+        fourth::sort(words, order)?;
+    } else {
+        segment::sort_segments(words, &[0, words.len()], order)?;
+    }
+
+    if swap {
+        words.par_iter_mut().for_each(|w| *w = w.swap_bytes());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort_file_in_place, ElemType, Endian};
+    use crate::utils::new_u32_vec;
+    use crate::SortOrder::*;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    /// テストごとに異なる一時ファイルのパスを返す
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("bitonic-sorter-{}-{}", process::id(), name))
+    }
+
+    #[test]
+    fn sort_u32_little_endian() {
+        let path = temp_path("u32le");
+        let mut x = new_u32_vec(1 << 16);
+        let bytes: Vec<u8> = x.iter().flat_map(|v| v.to_le_bytes()).collect();
+        fs::write(&path, bytes).unwrap();
+
+        let result = sort_file_in_place(&path, ElemType::U32(Endian::Little), &Ascending);
+
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result, Ok(()));
+        let actual: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        x.sort();
+        assert_eq!(actual, x);
+    }
+
+    // 要素数が2^nではないビッグエンディアンのファイル
+    #[test]
+    fn sort_u64_big_endian_not_power_of_two() {
+        let path = temp_path("u64be");
+        let mut x: Vec<u64> = new_u32_vec(1000)
+            .into_iter()
+            .map(|v| u64::from(v) << 20 | u64::from(v >> 24))
+            .collect();
+        let bytes: Vec<u8> = x.iter().flat_map(|v| v.to_be_bytes()).collect();
+        fs::write(&path, bytes).unwrap();
+
+        let result = sort_file_in_place(&path, ElemType::U64(Endian::Big), &Descending);
+
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result, Ok(()));
+        let actual: Vec<u64> = bytes
+            .chunks_exact(8)
+            .map(|b| u64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+            .collect();
+        x.sort_by(|a, b| b.cmp(a));
+        assert_eq!(actual, x);
+    }
+
+    #[test]
+    fn sort_empty_file() {
+        let path = temp_path("empty");
+        fs::write(&path, b"").unwrap();
+
+        let result = sort_file_in_place(&path, ElemType::U32(Endian::Little), &Ascending);
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn length_not_multiple_of_element_size() {
+        let path = temp_path("odd");
+        fs::write(&path, [1u8, 2, 3, 4, 5, 6]).unwrap();
+
+        let result = sort_file_in_place(&path, ElemType::U32(Endian::Big), &Ascending);
+
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+        assert_eq!(bytes, vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn missing_file() {
+        let path = temp_path("missing");
+        assert!(sort_file_in_place(&path, ElemType::U64(Endian::Little), &Ascending).is_err());
+    }
+}
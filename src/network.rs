@@ -0,0 +1,216 @@
+use crate::SortOrder;
+use crate::SortOrder::*;
+use std::cmp::Ordering;
+use std::cmp::Ordering::*;
+
+/// 展開済みのソーティングネットワークを持つ固定長配列
+/// 要素数が0と、64以下の2^nの配列に実装される。実装はbuild.rsがビルド時に生成する
+/// 比較器の添字はすべて定数なので、ループも実行時の添字の計算も境界検査もないコードになる
+pub trait SortingNetwork<T> {
+    /// 配列をソートする
+    /// # 引数
+    /// - comparator : 大小比較するためのクロージャ
+    fn sort_network<F: Fn(&T, &T) -> Ordering>(&mut self, comparator: &F);
+
+    /// バイトニック列を昇順にソートする
+    /// # 引数
+    /// - comparator : 大小比較するためのクロージャ
+    fn merge_network<F: Fn(&T, &T) -> Ordering>(&mut self, comparator: &F);
+}
+
+include!(concat!(env!("OUT_DIR"), "/networks.rs"));
+
+/// 固定長配列をソートする。要素数が2^nではない場合や、64を超える場合はコンパイルエラーになる
+/// # 引数
+/// - array : 固定長配列。ただし、要素数は2^nでなければならない。また、要素はOrdを実装しなければならない。
+/// - order : ソート順
+///
+/// ```compile_fail
+/// use bitonic_sorter::network::sort_array;
+/// use bitonic_sorter::SortOrder::Ascending;
+///
+/// let mut array = [3u32, 1, 2];
+/// sort_array(&mut array, &Ascending);
+/// ```
+pub fn sort_array<T: Ord, const N: usize>(array: &mut [T; N], order: &SortOrder)
+where
+    [T; N]: SortingNetwork<T>,
+{
+    match *order {
+        Ascending => sort_array_by(array, &|a, b| a.cmp(b)),
+        Descending => sort_array_by(array, &|a, b| b.cmp(a)),
+    }
+}
+
+/// 固定長配列をソートする。要素数が2^nではない場合や、64を超える場合はコンパイルエラーになる
+/// 比較器の並びはビルド時に生成した、比較＆入れ替えを並べただけのコードで実行する
+/// # 引数
+/// - array : 固定長配列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ
+///
+/// ```compile_fail
+/// use bitonic_sorter::network::sort_array_by;
+///
+/// let mut array = [0u32; 128];
+/// sort_array_by(&mut array, &|a: &u32, b: &u32| a.cmp(b));
+/// ```
+pub fn sort_array_by<T, F, const N: usize>(array: &mut [T; N], comparator: &F)
+where
+    [T; N]: SortingNetwork<T>,
+    F: Fn(&T, &T) -> Ordering,
+{
+    array.sort_network(comparator);
+}
+
+/// 再帰を使わず、ループだけで比較器の順序をたどって配列をソートする
+/// 外側のループでブロックの大きさkを、内側のループで比較する要素間の距離jを回し、
+/// 各要素iをi ^ jの要素と比較＆入れ替えする
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない
+/// - comparator : 比較のためのクロージャ
+pub(crate) fn sort_slice_by<T, F>(array: &mut [T], comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let n = array.len();
+
+    // k : バイトニック列を作るブロックの大きさ、j : 比較する要素間の距離
+    let mut k = 2;
+    while k <= n {
+        let mut j = k / 2;
+        while j > 0 {
+            for i in 0..n {
+                let partner = i ^ j;
+                if partner > i {
+                    // ブロック内の位置がkの前半なら昇順、後半なら降順
+                    compare_and_swap(array, comparator, i, partner, i & k == 0);
+                }
+            }
+            j /= 2;
+        }
+        k *= 2;
+    }
+}
+
+/// 固定長のバイトニック列をソートする。要素数が2^nではない場合や、64を超える場合はコンパイルエラーになる
+/// # 引数
+/// - bitonic_array : 固定長のバイトニック列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ
+pub fn merge_array_by<T, F, const N: usize>(bitonic_array: &mut [T; N], comparator: &F)
+where
+    [T; N]: SortingNetwork<T>,
+    F: Fn(&T, &T) -> Ordering,
+{
+    bitonic_array.merge_network(comparator);
+}
+
+/// 2つの要素を比較し、昇順か降順かに応じて並べ替える
+/// # 引数
+/// - array : 並び替え対象の配列
+/// - comparator : 比較のためのクロージャ
+/// - i, j : 比較する要素の添字（i < j）
+/// - forward : 昇順の場合true, 降順の場合false
+#[inline(always)]
+pub(crate) fn compare_and_swap<T, F>(
+    array: &mut [T],
+    comparator: &F,
+    i: usize,
+    j: usize,
+    forward: bool,
+) where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let expected = if forward { Greater } else { Less };
+    if comparator(&array[i], &array[j]) == expected {
+        array.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_array_by, sort_array, sort_array_by};
+    use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use crate::SortOrder::*;
+    use std::convert::TryInto;
+
+    // 昇順ソートのテスト
+    #[test]
+    fn sort_u32_ascending() {
+        let mut array: [u32; 8] = [10, 30, 11, 20, 4, 330, 21, 110];
+
+        sort_array(&mut array, &Ascending);
+
+        assert_eq!(array, [4, 10, 11, 20, 21, 30, 110, 330]);
+    }
+
+    //　降順ソートのテスト
+    #[test]
+    fn sort_u32_descending() {
+        let mut array: [u32; 8] = [10, 30, 11, 20, 4, 330, 21, 110];
+
+        sort_array(&mut array, &Descending);
+
+        assert_eq!(array, [330, 110, 30, 21, 20, 11, 10, 4]);
+    }
+
+    // 要素が0個
+    #[test]
+    fn sort_zero() {
+        let mut array: [u32; 0] = [];
+
+        sort_array(&mut array, &Ascending);
+
+        assert_eq!(array, []);
+    }
+
+    // 要素が1個
+    #[test]
+    fn sort_one() {
+        let mut array: [u32; 1] = [45];
+
+        sort_array(&mut array, &Descending);
+
+        assert_eq!(array, [45]);
+    }
+
+    #[test]
+    fn sort_str_by_length() {
+        let mut array = ["Rust", "is", "fast", "and"];
+
+        sort_array_by(&mut array, &|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+
+        assert_eq!(array, ["is", "and", "Rust", "fast"]);
+    }
+
+    // 16, 32要素の配列
+    #[test]
+    fn sort_u32_16_and_32() {
+        let mut x16: [u32; 16] = new_u32_vec(16).try_into().unwrap();
+        let mut x32: [u32; 32] = new_u32_vec(32).try_into().unwrap();
+
+        sort_array(&mut x16, &Ascending);
+        sort_array(&mut x32, &Descending);
+
+        assert!(is_sorted_ascending(&x16));
+        assert!(is_sorted_descending(&x32));
+    }
+
+    // ネットワークを生成する最大の要素数
+    #[test]
+    fn sort_u32_64() {
+        let mut x64: [u32; 64] = new_u32_vec(64).try_into().unwrap();
+
+        sort_array(&mut x64, &Descending);
+
+        assert!(is_sorted_descending(&x64));
+    }
+
+    #[test]
+    fn merge_bitonic_array() {
+        let mut array: [u32; 8] = [4, 11, 30, 330, 110, 21, 20, 10];
+
+        merge_array_by(&mut array, &|a, b| a.cmp(b));
+
+        assert_eq!(array, [4, 10, 11, 20, 21, 30, 110, 330]);
+    }
+}
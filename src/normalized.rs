@@ -0,0 +1,280 @@
+use crate::fourth;
+use crate::utils::{apply_indices, byte_prefix};
+use std::cmp::Ordering;
+
+/// バイト列の辞書順が値の大小と一致するように符号化できるキー
+/// 符号化したバイト列は、どの値のものも他の値のものの接頭辞にならない
+/// そのため、タプルの要素を続けて符号化しても、辞書順が要素ごとの比較と一致する
+pub trait NormalizedKey {
+    /// 値を符号化し、outの末尾に追加する
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// 値を符号化したバイト列を返す
+    fn to_normalized(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+}
+
+/// 降順に比較するためのキー。符号化したバイト列の各ビットを反転する
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Desc<K>(pub K);
+
+impl<K: NormalizedKey> NormalizedKey for Desc<K> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let start = out.len();
+        self.0.encode(out);
+        for byte in &mut out[start..] {
+            *byte = !*byte;
+        }
+    }
+}
+
+impl<K: NormalizedKey + ?Sized> NormalizedKey for &K {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (**self).encode(out)
+    }
+}
+
+impl NormalizedKey for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+macro_rules! impl_unsigned {
+    ($($t:ty),*) => {$(
+        impl NormalizedKey for $t {
+            fn encode(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_be_bytes());
+            }
+        }
+    )*};
+}
+
+impl_unsigned!(u8, u16, u32, u64, u128, usize);
+
+macro_rules! impl_signed {
+    ($($t:ty => $u:ty),*) => {$(
+        impl NormalizedKey for $t {
+            // 符号ビットを反転すると、負の数が正の数より小さくなる
+            fn encode(&self, out: &mut Vec<u8>) {
+                let bits = (*self as $u) ^ (1 << (<$u>::BITS - 1));
+                out.extend_from_slice(&bits.to_be_bytes());
+            }
+        }
+    )*};
+}
+
+impl_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128, isize => usize);
+
+macro_rules! impl_float {
+    ($($t:ty => $u:ty),*) => {$(
+        impl NormalizedKey for $t {
+            // total_cmpと同じ順序になるように、負の数は全ビットを、それ以外は符号ビットを反転する
+            fn encode(&self, out: &mut Vec<u8>) {
+                let bits = self.to_bits();
+                let sign = 1 << (<$u>::BITS - 1);
+                let bits = if bits & sign != 0 { !bits } else { bits | sign };
+                out.extend_from_slice(&bits.to_be_bytes());
+            }
+        }
+    )*};
+}
+
+impl_float!(f32 => u32, f64 => u64);
+
+impl NormalizedKey for [u8] {
+    // 0x00を0x00 0xFFに置き換え、0x00 0x00で終端する
+    // 終端が他のどのバイト列よりも小さいので、短い方が先になる
+    fn encode(&self, out: &mut Vec<u8>) {
+        for &byte in self {
+            out.push(byte);
+            if byte == 0 {
+                out.push(0xFF);
+            }
+        }
+        out.extend_from_slice(&[0, 0]);
+    }
+}
+
+impl NormalizedKey for str {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_bytes().encode(out)
+    }
+}
+
+impl NormalizedKey for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_str().encode(out)
+    }
+}
+
+impl<K: NormalizedKey> NormalizedKey for Option<K> {
+    // Noneを最小とする
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(key) => {
+                out.push(1);
+                key.encode(out);
+            }
+        }
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident),*) => {
+        impl<$($name: NormalizedKey),*> NormalizedKey for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn encode(&self, out: &mut Vec<u8>) {
+                let ($($name,)*) = self;
+                $($name.encode(out);)*
+            }
+        }
+    };
+}
+
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+impl_tuple!(A, B, C, D, E, F);
+
+/// 符号化したキーを比較して配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// 先頭8バイトを整数として比較し、等しい場合だけ符号化したキー全体を比較する
+/// 降順にする場合は、キーをDescで包む
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - key : 要素からキーを取り出すクロージャ。キーはNormalizedKeyを実装しなければならない。
+pub fn sort_by_normalized_key<T, K, F>(array: &mut [T], key: &F) -> Result<(), String>
+where
+    K: NormalizedKey,
+    F: Fn(&T) -> K,
+{
+    // すべてのキーを1つのバッファに続けて符号化する
+    let mut bytes = Vec::new();
+    let mut offsets = Vec::with_capacity(array.len() + 1);
+    offsets.push(0);
+    for element in array.iter() {
+        key(element).encode(&mut bytes);
+        offsets.push(bytes.len());
+    }
+    let normalized = |i: usize| &bytes[offsets[i]..offsets[i + 1]];
+
+    let mut pairs: Vec<(u64, usize)> = (0..array.len())
+        .map(|i| (byte_prefix(normalized(i)), i))
+        .collect();
+    fourth::sort_by(&mut pairs, &|a, b| match a.0.cmp(&b.0) {
+        Ordering::Equal => normalized(a.1).cmp(normalized(b.1)),
+        ordering => ordering,
+    })?;
+
+    let mut indices: Vec<usize> = pairs.into_iter().map(|(_, i)| i).collect();
+    apply_indices(array, &mut indices);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort_by_normalized_key, Desc, NormalizedKey};
+    use crate::third;
+    use crate::utils::new_u32_vec;
+    use std::cmp::Ordering;
+
+    /// 符号化したバイト列の順序が、値の順序と一致するか検査する
+    fn assert_order<K: NormalizedKey>(values: &[K], cmp: impl Fn(&K, &K) -> Ordering) {
+        for a in values {
+            for b in values {
+                assert_eq!(a.to_normalized().cmp(&b.to_normalized()), cmp(a, b));
+            }
+        }
+    }
+
+    #[test]
+    fn encode_integers() {
+        assert_order(&[0u32, 1, 255, 256, u32::MAX], |a, b| a.cmp(b));
+        assert_order(&[i64::MIN, -256, -1, 0, 1, 255, i64::MAX], |a, b| a.cmp(b));
+        assert_order(&[i8::MIN, -1, 0, i8::MAX], |a, b| a.cmp(b));
+    }
+
+    #[test]
+    fn encode_floats() {
+        let values = [
+            f64::NEG_INFINITY,
+            -1.5,
+            -0.0,
+            0.0,
+            f64::MIN_POSITIVE,
+            2.5,
+            f64::INFINITY,
+            f64::NAN,
+        ];
+        assert_order(&values, |a, b| a.total_cmp(b));
+    }
+
+    #[test]
+    fn encode_strings() {
+        let values = ["", "\0", "\0\0", "a", "a\0", "a\0b", "ab", "b", "\u{3042}"];
+        assert_order(&values, |a, b| a.cmp(b));
+    }
+
+    #[test]
+    fn encode_tuples_and_options() {
+        let values = [
+            (None, "z".to_string()),
+            (Some(""), "b".to_string()),
+            (Some(""), "c".to_string()),
+            (Some("a"), "a".to_string()),
+            (Some("a\0"), "".to_string()),
+        ];
+        assert_order(&values, |a, b| a.cmp(b));
+    }
+
+    #[test]
+    fn encode_descending() {
+        let values = [
+            (Desc("ab"), 1u8),
+            (Desc("ab"), 2),
+            (Desc("a"), 0),
+            (Desc(""), 5),
+        ];
+        assert_order(&values, |a, b| b.0 .0.cmp(a.0 .0).then(a.1.cmp(&b.1)));
+    }
+
+    #[test]
+    fn sort_records_same_as_third() {
+        let mut x: Vec<(i32, Option<String>, f64)> = new_u32_vec(1 << 12)
+            .into_iter()
+            .map(|v| {
+                let name = if v % 7 == 0 {
+                    None
+                } else {
+                    Some(format!("name{}", v % 50))
+                };
+                ((v % 5) as i32 - 2, name, f64::from(v) / 3.0)
+            })
+            .collect();
+        let mut expected = x.clone();
+
+        let key = |r: &(i32, Option<String>, f64)| (Desc(r.0), r.1.clone(), r.2);
+        assert_eq!(sort_by_normalized_key(&mut x, &key), Ok(()));
+
+        let comparator = |a: &(i32, Option<String>, f64), b: &(i32, Option<String>, f64)| {
+            b.0.cmp(&a.0)
+                .then_with(|| a.1.cmp(&b.1))
+                .then_with(|| a.2.total_cmp(&b.2))
+        };
+        assert!(third::sort_by(&mut expected, &comparator).is_ok());
+
+        assert_eq!(x, expected);
+    }
+
+    #[test]
+    fn sort_elemtns_not_power_of_two() {
+        let mut x = vec![3u32, 1, 2];
+        assert!(sort_by_normalized_key(&mut x, &|&v: &u32| v).is_err());
+    }
+}
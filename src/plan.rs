@@ -0,0 +1,190 @@
+use crate::fourth::PARALLEL_THRESHOLD;
+use crate::SortOrder;
+use crate::SortOrder::*;
+use rayon::prelude::*;
+use std::cmp::Ordering::*;
+
+/// 比較器の1層分の情報
+struct Layer {
+    /// バイトニック列を作るブロックの大きさ
+    block: usize,
+    /// 比較する要素間の距離
+    stride: usize,
+    /// 1タスクが受け持つ要素数。要素数以上の場合は直列に処理する
+    task_len: usize,
+}
+
+/// 同じ要素数の配列を繰り返しソートするための実行計画
+/// 比較器の順序と並列化の分割をあらかじめ計算しておき、ソートのたびに再利用する
+pub struct SortPlan {
+    len: usize,
+    forward: bool,
+    layers: Vec<Layer>,
+}
+
+impl SortPlan {
+    /// 実行計画を作る。要素数が2^nではない場合、エラーを返す
+    /// # 引数
+    /// - n : ソートする配列の要素数。2^nでなければならない
+    /// - order : ソート順
+    pub fn new(n: usize, order: &SortOrder) -> Result<Self, String> {
+        if n != 0 && !n.is_power_of_two() {
+            return Err(format!(
+                "The length of array is not a power of two. (array.len(): {})",
+                n
+            ));
+        }
+
+        let mut layers = Vec::new();
+        let mut block = 2;
+        while block <= n {
+            let mut stride = block / 2;
+            while stride > 0 {
+                // 比較する要素の組は2 * strideの範囲に閉じているので、その倍数で分割する
+                let task_len = (2 * stride).max(PARALLEL_THRESHOLD);
+                layers.push(Layer {
+                    block,
+                    stride,
+                    task_len,
+                });
+                stride /= 2;
+            }
+            block *= 2;
+        }
+
+        Ok(Self {
+            len: n,
+            forward: matches!(*order, Ascending),
+            layers,
+        })
+    }
+
+    /// 実行計画が対象とする要素数を返す
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// 実行計画が対象とする要素数が0かどうかを返す
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 実行計画にしたがって配列をソートする
+    /// # 引数
+    /// - array : 配列。要素数は実行計画を作ったときと同じでなければならない
+    /// # 戻り値
+    /// - 要素数が実行計画と一致する場合Okを、それ以外の場合Errを返す
+    pub fn execute<T: Ord + Send>(&self, array: &mut [T]) -> Result<(), String> {
+        if array.len() != self.len {
+            return Err(format!(
+                "The length of array does not match the plan. (array.len(): {}, plan.len(): {})",
+                array.len(),
+                self.len
+            ));
+        }
+
+        for layer in &self.layers {
+            if layer.task_len < self.len {
+                array
+                    .par_chunks_mut(layer.task_len)
+                    .enumerate()
+                    .for_each(|(t, task)| self.run_layer(layer, task, t * layer.task_len));
+            } else {
+                self.run_layer(layer, array, 0);
+            }
+        }
+        Ok(())
+    }
+
+    /// 1層分の比較＆入れ替えを行う
+    /// # 引数
+    /// - layer : 比較器の層
+    /// - task : 処理する範囲
+    /// - offset : 処理する範囲の配列全体での開始位置
+    fn run_layer<T: Ord>(&self, layer: &Layer, task: &mut [T], offset: usize) {
+        let width = 2 * layer.stride;
+        for (c, chunk) in task.chunks_mut(width).enumerate() {
+            // ブロック内の位置が前半なら指定の順、後半なら逆順
+            let forward = ((offset + c * width) & layer.block == 0) == self.forward;
+            let expected = if forward { Greater } else { Less };
+
+            let (first, second) = chunk.split_at_mut(layer.stride);
+            for (a, b) in first.iter_mut().zip(second.iter_mut()) {
+                if (*a).cmp(b) == expected {
+                    std::mem::swap(a, b);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SortPlan;
+    use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use crate::SortOrder::*;
+
+    // 昇順ソートのテスト
+    #[test]
+    fn sort_u32_ascending() {
+        let plan = SortPlan::new(8, &Ascending).unwrap();
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+
+        assert!(plan.execute(&mut array).is_ok());
+
+        assert_eq!(array, vec![4, 10, 11, 20, 21, 30, 110, 330]);
+    }
+
+    //　降順ソートのテスト
+    #[test]
+    fn sort_u32_descending() {
+        let plan = SortPlan::new(8, &Descending).unwrap();
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+
+        assert!(plan.execute(&mut array).is_ok());
+
+        assert_eq!(array, vec![330, 110, 30, 21, 20, 11, 10, 4]);
+    }
+
+    // 要素が0個
+    #[test]
+    fn sort_zero() {
+        let plan = SortPlan::new(0, &Ascending).unwrap();
+        let mut array: Vec<u32> = vec![];
+
+        assert!(plan.execute(&mut array).is_ok());
+
+        assert_eq!(array, vec![]);
+    }
+
+    // 要素が2のべき乗個ではない場合
+    #[test]
+    fn new_not_power_of_two() {
+        assert!(SortPlan::new(3, &Ascending).is_err());
+    }
+
+    // 要素数が実行計画と一致しない場合
+    #[test]
+    fn execute_len_mismatch() {
+        let plan = SortPlan::new(4, &Ascending).unwrap();
+        let mut array: Vec<u32> = vec![45, 21];
+
+        assert!(plan.execute(&mut array).is_err());
+    }
+
+    // 実行計画を再利用する
+    #[test]
+    fn reuse_plan() {
+        let plan = SortPlan::new(1 << 16, &Ascending).unwrap();
+        let reverse = SortPlan::new(1 << 16, &Descending).unwrap();
+
+        for _ in 0..3 {
+            let mut x = new_u32_vec(1 << 16);
+            assert_eq!(plan.execute(&mut x), Ok(()));
+            assert!(is_sorted_ascending(&x));
+
+            assert_eq!(reverse.execute(&mut x), Ok(()));
+            assert!(is_sorted_descending(&x));
+        }
+    }
+}
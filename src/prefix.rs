@@ -0,0 +1,104 @@
+use crate::fourth;
+use crate::utils::{apply_indices, byte_prefix};
+use crate::SortOrder;
+use crate::SortOrder::*;
+use std::cmp::Ordering;
+
+/// 文字列の配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// 各文字列の先頭8バイトを整数としてキャッシュし、キャッシュと添字の組をソートする
+/// 先頭8バイトが等しい場合だけ、文字列全体を比較する
+/// # 引数
+/// - array : 文字列の配列。ただし、要素数は2^nでなければならない。StringやVec<u8>などを並べられる
+/// - order : ソート順。バイト列の辞書順で比較する
+pub fn sort<S>(array: &mut [S], order: &SortOrder) -> Result<(), String>
+where
+    S: AsRef<[u8]> + Sync,
+{
+    let mut pairs: Vec<(u64, usize)> = array
+        .iter()
+        .map(|s| byte_prefix(s.as_ref()))
+        .zip(0..)
+        .collect();
+    {
+        let array = &*array;
+        let comparator = |a: &(u64, usize), b: &(u64, usize)| match a.0.cmp(&b.0) {
+            Ordering::Equal => array[a.1].as_ref().cmp(array[b.1].as_ref()),
+            ordering => ordering,
+        };
+        match *order {
+            Ascending => fourth::sort_by(&mut pairs, &comparator)?,
+            Descending => fourth::sort_by(&mut pairs, &|a, b| comparator(b, a))?,
+        }
+    }
+
+    let mut indices: Vec<usize> = pairs.into_iter().map(|(_, i)| i).collect();
+    apply_indices(array, &mut indices);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sort;
+    use crate::third;
+    use crate::utils::{byte_prefix, new_u32_vec};
+    use crate::SortOrder::*;
+
+    #[test]
+    fn prefix_order() {
+        assert!(byte_prefix(b"") < byte_prefix(b"\x01"));
+        assert!(byte_prefix(b"abc") < byte_prefix(b"abd"));
+        assert!(byte_prefix(b"abcdefgh") < byte_prefix(b"abcdefgi"));
+        // 8バイトを超える部分と、末尾の0は区別できない
+        assert_eq!(byte_prefix(b"abcdefghX"), byte_prefix(b"abcdefghY"));
+        assert_eq!(byte_prefix(b"a"), byte_prefix(b"a\0"));
+    }
+
+    #[test]
+    fn sort_str_ascending() {
+        let mut x = vec![
+            "and",
+            "Rust",
+            "hello",
+            "is",
+            "fast",
+            "",
+            "and",
+            "safe",
+            "a\0",
+            "a",
+            "concurrent",
+            "concurrency",
+            "!",
+            "ascending",
+            "asc",
+            "\u{3042}",
+        ];
+        let mut expected = x.clone();
+
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        assert!(third::sort(&mut expected, &Ascending).is_ok());
+
+        assert_eq!(x, expected);
+    }
+
+    // 先頭8バイトが同じ文字列が多い場合
+    #[test]
+    fn sort_strings_with_common_prefix() {
+        let mut x: Vec<String> = new_u32_vec(1 << 14)
+            .into_iter()
+            .map(|v| format!("/var/log/app-{}.log", v % 3000))
+            .collect();
+        let mut expected = x.clone();
+
+        assert_eq!(sort(&mut x, &Descending), Ok(()));
+        assert!(third::sort(&mut expected, &Descending).is_ok());
+
+        assert_eq!(x, expected);
+    }
+
+    #[test]
+    fn sort_elemtns_not_power_of_two() {
+        let mut x = vec!["b", "c", "a"];
+        assert!(sort(&mut x, &Ascending).is_err());
+    }
+}
@@ -0,0 +1,436 @@
+use crate::fourth;
+use crate::utils::apply_indices_with;
+use crate::SortOrder;
+use crate::SortOrder::*;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+
+/// gensortのレコードの長さ
+pub const RECORD_LEN: usize = 100;
+/// gensortのレコードのキーの長さ
+pub const KEY_LEN: usize = 10;
+
+/// 固定長レコードの形式。レコードの中のキーの位置と長さを指定する
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordLayout {
+    record_len: usize,
+    key_offset: usize,
+    key_len: usize,
+}
+
+impl RecordLayout {
+    /// gensortの形式。100バイトのレコードの先頭10バイトがキー
+    pub const GENSORT: Self = Self {
+        record_len: RECORD_LEN,
+        key_offset: 0,
+        key_len: KEY_LEN,
+    };
+
+    /// レコードの形式を作る。キーがレコードに収まらない場合、エラーを返す
+    /// # 引数
+    /// - record_len : レコードの長さ（バイト数）
+    /// - key_offset : レコードの先頭からキーまでのバイト数
+    /// - key_len : キーの長さ（バイト数）
+    pub fn new(record_len: usize, key_offset: usize, key_len: usize) -> Result<Self, String> {
+        let fits = key_offset
+            .checked_add(key_len)
+            .is_some_and(|key_end| key_end <= record_len);
+        if record_len == 0 || !fits {
+            return Err(format!(
+                "The key does not fit in the record. (record: {}, key offset: {}, key length: {})",
+                record_len, key_offset, key_len
+            ));
+        }
+        Ok(Self {
+            record_len,
+            key_offset,
+            key_len,
+        })
+    }
+
+    pub fn record_len(&self) -> usize {
+        self.record_len
+    }
+
+    /// レコードのキーを返す
+    fn key<'a>(&self, record: &'a [u8]) -> &'a [u8] {
+        &record[self.key_offset..self.key_offset + self.key_len]
+    }
+
+    /// データに含まれるレコードの数を返す。データの長さがレコードの長さの倍数ではない場合、エラーを返す
+    fn count(&self, data: &[u8]) -> Result<usize, String> {
+        if data.len().is_multiple_of(self.record_len) {
+            Ok(data.len() / self.record_len)
+        } else {
+            Err(format!(
+                "The data length is not a multiple of the record length. (data: {}, record: {})",
+                data.len(),
+                self.record_len
+            ))
+        }
+    }
+}
+
+impl Default for RecordLayout {
+    fn default() -> Self {
+        Self::GENSORT
+    }
+}
+
+/// バイト列を固定長レコードの並びとみなし、キーのバイト列の辞書順にその場でソートする
+/// レコードの数は2^nでなくてもよい。キーと添字の組を2^n個になるまで詰めてからfourthでソートする
+/// キーが等しいレコードは、元の順序を保つ
+/// # 引数
+/// - data : レコードを並べたバイト列。長さはレコードの長さの倍数でなければならない
+/// - layout : レコードの形式
+/// - order : ソート順
+pub fn sort_records(
+    data: &mut [u8],
+    layout: &RecordLayout,
+    order: &SortOrder,
+) -> Result<(), String> {
+    let count = layout.count(data)?;
+
+    let mut indices = {
+        let mut keys: Vec<Option<(&[u8], usize)>> = data
+            .chunks_exact(layout.record_len)
+            .map(|record| layout.key(record))
+            .zip(0..)
+            .map(Some)
+            .collect();
+        keys.resize(count.next_power_of_two(), None);
+
+        let forward = *order == Ascending;
+        fourth::sort_by(&mut keys, &|a, b| match (a, b) {
+            (Some(a), Some(b)) if forward => a.0.cmp(b.0).then(a.1.cmp(&b.1)),
+            (Some(a), Some(b)) => b.0.cmp(a.0).then(a.1.cmp(&b.1)),
+            // 詰めたNoneはソート順によらず末尾に置く
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        })?;
+
+        keys.into_iter()
+            .flatten()
+            .map(|(_, i)| i)
+            .collect::<Vec<_>>()
+    };
+
+    apply_indices_with(&mut indices, |i, j| {
+        swap_records(data, layout.record_len, i, j)
+    });
+    Ok(())
+}
+
+/// i番目とj番目のレコードを入れ替える
+fn swap_records(data: &mut [u8], record_len: usize, i: usize, j: usize) {
+    if i == j {
+        return;
+    }
+    let (low, high) = (i.min(j), i.max(j));
+    let (head, tail) = data.split_at_mut(high * record_len);
+    head[low * record_len..(low + 1) * record_len].swap_with_slice(&mut tail[..record_len]);
+}
+
+/// gensortの乱数生成器の乗数と加数。法2^128の線形合同法 x' = A * x + C で乱数を作る
+const LCG: (u128, u128) = (
+    0x2360_ed05_1fc6_5da4_4385_df64_9fcc_f645,
+    0x4a69_6d47_7261_7952_4950_2020_2020_2001,
+);
+
+/// LCG_POWERS[i]は、線形合同法を2^i回適用する関数の乗数と加数
+const LCG_POWERS: [(u128, u128); 128] = lcg_powers();
+
+const fn lcg_powers() -> [(u128, u128); 128] {
+    let mut powers = [LCG; 128];
+    let mut i = 1;
+    while i < 128 {
+        // f(f(x)) = a * (a * x + c) + c
+        let (a, c) = powers[i - 1];
+        powers[i] = (a.wrapping_mul(a), a.wrapping_mul(c).wrapping_add(c));
+        i += 1;
+    }
+    powers
+}
+
+/// 0から線形合同法をadvance回進めた乱数を返す。advanceのビットごとに2^i回分をまとめて進める
+fn skip_ahead(advance: u128) -> u128 {
+    (0..128)
+        .filter(|i| advance >> i & 1 == 1)
+        .fold(0, |rand, i| {
+            let (a, c) = LCG_POWERS[i];
+            a.wrapping_mul(rand).wrapping_add(c)
+        })
+}
+
+/// 線形合同法で次の乱数を返す
+fn next_rand(rand: u128) -> u128 {
+    LCG.0.wrapping_mul(rand).wrapping_add(LCG.1)
+}
+
+/// 並列に生成するときの、1つのタスクが生成するレコードの数
+const GENERATE_CHUNK: usize = 4096;
+
+/// gensortがバイナリ形式で出力するものと同じレコードを生成する
+/// レコードはキー10バイト、0x00 0x11、16進数32桁のレコード番号、0x88 0x99 0xAA 0xBB、
+/// 埋め草48バイト、0xCC 0xDD 0xEE 0xFFからなる
+/// gensortと同じ乱数生成器をレコード番号だけ進めて使うので、出力はgensortとバイト単位で一致し、
+/// validateのチェックサムもvalsortと一致する。生成を分割しても同じデータになる
+/// # 引数
+/// - first : 最初のレコードの番号（gensortの-bオプション）
+/// - count : 生成するレコードの数
+pub fn generate(first: u64, count: usize) -> Vec<u8> {
+    let mut data = vec![0u8; count * RECORD_LEN];
+    data.par_chunks_mut(GENERATE_CHUNK * RECORD_LEN)
+        .enumerate()
+        .for_each(|(i, chunk)| {
+            let start = u128::from(first) + (i * GENERATE_CHUNK) as u128;
+            let mut rand = skip_ahead(start);
+            for (number, record) in (start..).zip(chunk.chunks_exact_mut(RECORD_LEN)) {
+                rand = next_rand(rand);
+                write_record(record, rand, number);
+            }
+        });
+    data
+}
+
+/// 1つのレコードを書き出す
+/// # 引数
+/// - record : 書き出し先
+/// - rand : このレコードの乱数。上位10バイトがキーに、下位48ビットが埋め草になる
+/// - number : レコード番号
+fn write_record(record: &mut [u8], rand: u128, number: u128) {
+    const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+    record[..KEY_LEN].copy_from_slice(&rand.to_be_bytes()[..KEY_LEN]);
+    record[10..12].copy_from_slice(&[0x00, 0x11]);
+    record[12..44].copy_from_slice(format!("{:032X}", number).as_bytes());
+    record[44..48].copy_from_slice(&[0x88, 0x99, 0xAA, 0xBB]);
+    // 埋め草は、乱数の下位48ビットを16進数の12文字にし、各文字を4回繰り返したもの
+    for (i, group) in record[48..96].chunks_exact_mut(4).enumerate() {
+        group.fill(HEX_DIGITS[(rand >> (44 - 4 * i) & 0xF) as usize]);
+    }
+    record[96..100].copy_from_slice(&[0xCC, 0xDD, 0xEE, 0xFF]);
+}
+
+/// valsortと同じ方法でレコードを検査した結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    /// レコードの数
+    pub records: u64,
+    /// 各レコードのCRC32の和。ソートの前後で一致すれば、レコードが失われていないことがわかる
+    pub checksum: u128,
+    /// 直前のレコードとキーが等しいレコードの数
+    pub duplicates: u64,
+    /// 直前のレコードよりキーが小さいレコードの数
+    pub unordered: u64,
+    /// 最初に見つかった、直前のレコードよりキーが小さいレコードの番号
+    pub first_unordered: Option<u64>,
+}
+
+impl Summary {
+    /// レコードがキーの昇順に並んでいるかどうかを返す
+    pub fn is_sorted(&self) -> bool {
+        self.unordered == 0
+    }
+}
+
+/// レコードがキーの昇順に並んでいるか検査し、チェックサムを計算する
+/// # 引数
+/// - data : レコードを並べたバイト列。長さはレコードの長さの倍数でなければならない
+/// - layout : レコードの形式
+pub fn validate(data: &[u8], layout: &RecordLayout) -> Result<Summary, String> {
+    let count = layout.count(data)?;
+
+    let checksum = data
+        .par_chunks(layout.record_len)
+        .map(|record| u128::from(crc32(record)))
+        .sum();
+
+    let mut summary = Summary {
+        records: count as u64,
+        checksum,
+        duplicates: 0,
+        unordered: 0,
+        first_unordered: None,
+    };
+    let records = data.chunks_exact(layout.record_len);
+    for (i, (a, b)) in records.clone().zip(records.skip(1)).enumerate() {
+        match layout.key(a).cmp(layout.key(b)) {
+            Ordering::Less => {}
+            Ordering::Equal => summary.duplicates += 1,
+            Ordering::Greater => {
+                summary.unordered += 1;
+                summary.first_unordered.get_or_insert(i as u64 + 1);
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// CRC32（IEEE 802.3、zlibと同じ多項式）の表
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        CRC32_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        crc32, generate, next_rand, skip_ahead, sort_records, validate, RecordLayout,
+        GENERATE_CHUNK, KEY_LEN, RECORD_LEN,
+    };
+    use crate::SortOrder::*;
+
+    #[test]
+    fn crc32_known_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    // gensort -b0 2が出力するレコード
+    #[test]
+    fn generate_same_as_gensort() {
+        let data = generate(0, 2);
+
+        let expected = [
+            &b"JimGrayRIP"[..],
+            &[0x00, 0x11],
+            b"00000000000000000000000000000000",
+            &[0x88, 0x99, 0xAA, 0xBB],
+            b"222200002222000022220000222200002222000000001111",
+            &[0xCC, 0xDD, 0xEE, 0xFF],
+            &[0x95, 0xE0, 0xE4, 0x82, 0x62, 0xB3, 0xED, 0xFE, 0x04, 0x47],
+            &[0x00, 0x11],
+            b"00000000000000000000000000000001",
+            &[0x88, 0x99, 0xAA, 0xBB],
+            b"9999444488885555CCCC777755555555BBBB666644446666",
+            &[0xCC, 0xDD, 0xEE, 0xFF],
+        ]
+        .concat();
+        assert_eq!(data, expected);
+    }
+
+    // 乱数生成器を先に進めて、途中のレコードから生成する
+    #[test]
+    fn generate_from_middle() {
+        let data = generate(0, 3 * GENERATE_CHUNK / 2);
+
+        let middle = GENERATE_CHUNK - 2;
+        assert_eq!(
+            generate(middle as u64, 5),
+            &data[middle * RECORD_LEN..(middle + 5) * RECORD_LEN]
+        );
+        assert_eq!(
+            &data[middle * RECORD_LEN + 12..middle * RECORD_LEN + 44],
+            format!("{:032X}", middle).as_bytes()
+        );
+    }
+
+    #[test]
+    fn skip_ahead_same_as_next_rand() {
+        let mut rand = 0;
+        for n in 0..300 {
+            assert_eq!(skip_ahead(n), rand);
+            rand = next_rand(rand);
+        }
+    }
+
+    // レコードの数が2^nではない場合
+    #[test]
+    fn sort_generated_records() {
+        let mut data = generate(0, 1000);
+        let before = validate(&data, &RecordLayout::GENSORT).unwrap();
+        assert!(!before.is_sorted());
+
+        assert_eq!(
+            sort_records(&mut data, &RecordLayout::default(), &Ascending),
+            Ok(())
+        );
+
+        let after = validate(&data, &RecordLayout::GENSORT).unwrap();
+        assert!(after.is_sorted());
+        assert_eq!(after.records, 1000);
+        assert_eq!(after.checksum, before.checksum);
+    }
+
+    #[test]
+    fn sort_records_by_key_offset() {
+        // 4バイトのレコードの、2バイト目から2バイトをキーとする
+        let mut data = vec![
+            b'a', 3, 0, b'x', //
+            b'b', 1, 9, b'y', //
+            b'c', 2, 0, b'z', //
+        ];
+        let layout = RecordLayout::new(4, 1, 2).unwrap();
+
+        assert_eq!(sort_records(&mut data, &layout, &Descending), Ok(()));
+
+        assert_eq!(
+            data,
+            vec![b'a', 3, 0, b'x', b'c', 2, 0, b'z', b'b', 1, 9, b'y']
+        );
+    }
+
+    // キーが等しいレコードは元の順序を保つ
+    #[test]
+    fn sort_records_stable() {
+        let mut data = vec![2, b'a', 1, b'b', 2, b'c', 1, b'd'];
+        let layout = RecordLayout::new(2, 0, 1).unwrap();
+
+        assert_eq!(sort_records(&mut data, &layout, &Ascending), Ok(()));
+
+        assert_eq!(data, vec![1, b'b', 1, b'd', 2, b'a', 2, b'c']);
+        assert_eq!(validate(&data, &layout).unwrap().duplicates, 2);
+    }
+
+    #[test]
+    fn validate_unordered() {
+        let mut data = generate(0, 4);
+        for (i, key) in [[1u8; KEY_LEN], [3; KEY_LEN], [2; KEY_LEN], [0; KEY_LEN]]
+            .iter()
+            .enumerate()
+        {
+            data[i * RECORD_LEN..i * RECORD_LEN + KEY_LEN].copy_from_slice(key);
+        }
+
+        let summary = validate(&data, &RecordLayout::GENSORT).unwrap();
+
+        assert_eq!(summary.unordered, 2);
+        assert_eq!(summary.first_unordered, Some(2));
+    }
+
+    #[test]
+    fn invalid_layout() {
+        assert!(RecordLayout::new(10, 5, 6).is_err());
+        assert!(RecordLayout::new(0, 0, 0).is_err());
+        // キーの終わりの位置がusizeに収まらない場合
+        assert!(RecordLayout::new(10, usize::MAX, 2).is_err());
+        assert!(RecordLayout::new(10, 2, usize::MAX).is_err());
+
+        let mut data = vec![0u8; 150];
+        assert!(sort_records(&mut data, &RecordLayout::GENSORT, &Ascending).is_err());
+        assert!(validate(&data, &RecordLayout::GENSORT).is_err());
+    }
+}
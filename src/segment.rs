@@ -0,0 +1,273 @@
+use crate::fourth::PARALLEL_THRESHOLD;
+use crate::SortOrder;
+use crate::SortOrder::*;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::cmp::Ordering::*;
+
+/// オフセット配列（CSR形式）で区切られた各区間を独立にソートする
+/// # 引数
+/// - array : 配列。要素はOrdを実装しなければならない。
+/// - offsets : 区間の境界。i番目の区間はoffsets[i]..offsets[i + 1]となる
+/// - order : ソート順
+/// # 戻り値
+/// - offsetsが単調非減少かつarrayの範囲内の場合Okを、それ以外の場合Errを返す
+pub fn sort_segments<T: Ord + Send>(
+    array: &mut [T],
+    offsets: &[usize],
+    order: &SortOrder,
+) -> Result<(), String> {
+    match *order {
+        Ascending => sort_segments_by(array, offsets, &|a, b| a.cmp(b)),
+        Descending => sort_segments_by(array, offsets, &|a, b| b.cmp(a)),
+    }
+}
+
+/// オフセット配列（CSR形式）で区切られた各区間を独立にソートする
+/// # 引数
+/// - array : 配列
+/// - offsets : 区間の境界。i番目の区間はoffsets[i]..offsets[i + 1]となる
+/// - comparator : 大小比較するためのクロージャ
+/// # 戻り値
+/// - offsetsが単調非減少かつarrayの範囲内の場合Okを、それ以外の場合Errを返す
+pub fn sort_segments_by<T, F>(
+    array: &mut [T],
+    offsets: &[usize],
+    comparator: &F,
+) -> Result<(), String>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let mut segments = split_segments(array, offsets)?;
+
+    // 区間の間を並列化する。大きな区間は区間の中でも並列化される
+    segments
+        .par_iter_mut()
+        .for_each(|segment| do_sort(segment, comparator, true));
+    Ok(())
+}
+
+/// オフセット配列にしたがって配列を互いに重ならない区間に分割する
+/// # 引数
+/// - array : 配列
+/// - offsets : 区間の境界
+/// # 戻り値
+/// - 各区間の可変スライス。offsetsが不正な場合Err
+fn split_segments<'a, T>(
+    array: &'a mut [T],
+    offsets: &[usize],
+) -> Result<Vec<&'a mut [T]>, String> {
+    let mut segments = Vec::with_capacity(offsets.len().saturating_sub(1));
+    let mut rest = match offsets.first() {
+        Some(&start) if start <= array.len() => &mut array[start..],
+        Some(&start) => {
+            return Err(format!(
+                "The offset is out of range. (offset: {}, array.len(): {})",
+                start,
+                array.len()
+            ))
+        }
+        None => return Ok(segments),
+    };
+
+    for pair in offsets.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        if end < start || end - start > rest.len() {
+            return Err(format!(
+                "The offsets are not monotonic or out of range. (start: {}, end: {})",
+                start, end
+            ));
+        }
+        let (segment, tail) = rest.split_at_mut(end - start);
+        segments.push(segment);
+        rest = tail;
+    }
+    Ok(segments)
+}
+
+/// 任意の要素数の配列をソートする
+/// 前半を逆順、後半を指定の順にソートしてバイトニック列を作る
+/// # 引数
+/// - array : 配列
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+fn do_sort<T, F>(array: &mut [T], comparator: &F, forward: bool)
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    if array.len() <= 1 {
+        return;
+    }
+
+    let mid = array.len() / 2;
+    let (first, second) = array.split_at_mut(mid);
+
+    if mid >= PARALLEL_THRESHOLD {
+        rayon::join(
+            || do_sort(first, comparator, !forward),
+            || do_sort(second, comparator, forward),
+        );
+    } else {
+        do_sort(first, comparator, !forward);
+        do_sort(second, comparator, forward);
+    }
+
+    sub_sort(array, comparator, forward);
+}
+
+/// 任意の要素数のバイトニック列をソートする
+/// 要素数未満の最大の2のべき乗mだけ離れた要素を比較＆入れ替えし、前のm個と残りに分ける
+/// # 引数
+/// - bitonic_array : バイトニック列
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+fn sub_sort<T, F>(bitonic_array: &mut [T], comparator: &F, forward: bool)
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let len = bitonic_array.len();
+    if len <= 1 {
+        return;
+    }
+
+    let m = len.next_power_of_two() / 2;
+    compare_and_swap(bitonic_array, comparator, m, forward);
+
+    let (first, second) = bitonic_array.split_at_mut(m);
+    if m >= PARALLEL_THRESHOLD {
+        rayon::join(
+            || sub_sort(first, comparator, forward),
+            || sub_sort(second, comparator, forward),
+        );
+    } else {
+        sub_sort(first, comparator, forward);
+        sub_sort(second, comparator, forward);
+    }
+}
+
+/// 各要素を距離mだけ右の要素と比較し、昇順か降順かに応じて並べ替える
+/// # 引数
+/// - array : 並び替え対象の配列
+/// - comparator : 比較のためのクロージャ
+/// - m : 比較する要素間の距離
+/// - forward : 昇順の場合true, 降順の場合false
+fn compare_and_swap<T, F>(array: &mut [T], comparator: &F, m: usize, forward: bool)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let expected = if forward { Greater } else { Less };
+    for i in 0..array.len() - m {
+        if comparator(&array[i], &array[i + m]) == expected {
+            array.swap(i, i + m);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort_segments, sort_segments_by};
+    use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use crate::SortOrder::*;
+
+    // 昇順ソートのテスト
+    #[test]
+    fn sort_u32_ascending() {
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110, 7];
+
+        assert!(sort_segments(&mut array, &[0, 3, 3, 8, 9], &Ascending).is_ok());
+
+        assert_eq!(array, vec![10, 11, 30, 4, 20, 21, 110, 330, 7]);
+    }
+
+    //　降順ソートのテスト
+    #[test]
+    fn sort_u32_descending() {
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110, 7];
+
+        assert!(sort_segments(&mut array, &[1, 6, 9], &Descending).is_ok());
+
+        assert_eq!(array, vec![10, 330, 30, 20, 11, 4, 110, 21, 7]);
+    }
+
+    // オフセットが空の場合
+    #[test]
+    fn sort_no_segments() {
+        let mut array: Vec<u32> = vec![3, 2, 1];
+
+        assert!(sort_segments(&mut array, &[], &Ascending).is_ok());
+
+        assert_eq!(array, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn sort_str_by_length() {
+        let mut array = vec![
+            "Rust",
+            "is",
+            "fast",
+            "and",
+            "memory-efficient",
+            "with",
+            "no",
+        ];
+
+        assert!(sort_segments_by(&mut array, &[0, 4, 7], &|a, b| a.len().cmp(&b.len())).is_ok());
+
+        assert_eq!(array[..2], ["is", "and"]);
+        assert_eq!(array[4..], ["no", "with", "memory-efficient"]);
+    }
+
+    // オフセットが単調非減少ではない場合
+    #[test]
+    fn sort_offsets_not_monotonic() {
+        let mut array: Vec<u32> = vec![45, 21, 11, 3, 5, 8];
+
+        assert!(sort_segments(&mut array, &[0, 4, 2, 6], &Ascending).is_err());
+    }
+
+    // オフセットが範囲外の場合
+    #[test]
+    fn sort_offsets_out_of_range() {
+        let mut array: Vec<u32> = vec![45, 21, 11, 3, 5, 8];
+
+        assert!(sort_segments(&mut array, &[0, 4, 7], &Ascending).is_err());
+        assert!(sort_segments(&mut array, &[7], &Ascending).is_err());
+    }
+
+    // あらゆる長さの区間
+    #[test]
+    fn sort_u32_every_length() {
+        let mut offsets = vec![0];
+        for len in 0..100 {
+            offsets.push(offsets.last().unwrap() + len);
+        }
+        let mut x = new_u32_vec(*offsets.last().unwrap());
+        let mut y = x.clone();
+
+        assert_eq!(sort_segments(&mut x, &offsets, &Ascending), Ok(()));
+        assert_eq!(sort_segments(&mut y, &offsets, &Descending), Ok(()));
+
+        for pair in offsets.windows(2) {
+            assert!(is_sorted_ascending(&x[pair[0]..pair[1]]));
+            assert!(is_sorted_descending(&y[pair[0]..pair[1]]));
+        }
+    }
+
+    // 並列化される大きな区間
+    #[test]
+    fn sort_u32_large_segments() {
+        let offsets = [0, 3, 50_000, 70_001, 100_000];
+        let mut x = new_u32_vec(100_000);
+        let mut expected = x.clone();
+
+        assert_eq!(sort_segments(&mut x, &offsets, &Ascending), Ok(()));
+
+        for pair in offsets.windows(2) {
+            expected[pair[0]..pair[1]].sort();
+        }
+        assert_eq!(x, expected);
+    }
+}
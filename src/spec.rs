@@ -0,0 +1,380 @@
+use crate::SortOrder;
+use crate::SortOrder::*;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// レコードのフィールドの値
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl Value {
+    /// 種類の異なる値を比較するための順位。Nullが最も小さい
+    fn rank(&self) -> u8 {
+        match *self {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Int(_) => 2,
+            Value::Float(_) => 3,
+            Value::Str(_) => 4,
+        }
+    }
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::Float(a), Value::Float(b)) => a.total_cmp(b),
+            (Value::Str(a), Value::Str(b)) => a.cmp(b),
+            // 整数と浮動小数点数は数として比較し、等しい場合は整数を先にする
+            (Value::Int(a), Value::Float(b)) => cmp_int_float(*a, *b).then(Ordering::Less),
+            (Value::Float(a), Value::Int(b)) => {
+                cmp_int_float(*b, *a).reverse().then(Ordering::Greater)
+            }
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+/// 整数と浮動小数点数を、丸めずに数として比較する。NaNはtotal_cmpと同じく両端に置く
+fn cmp_int_float(a: i64, b: f64) -> Ordering {
+    // 2^63。i64の範囲を超える値との比較に使う
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    if b.is_nan() {
+        return if b.is_sign_negative() {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        };
+    }
+    if b >= LIMIT {
+        return Ordering::Less;
+    }
+    if b < -LIMIT {
+        return Ordering::Greater;
+    }
+    let truncated = b.trunc();
+    a.cmp(&(truncated as i64)).then_with(|| {
+        if b > truncated {
+            Ordering::Less
+        } else if b < truncated {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    })
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+/// 名前でフィールドの値を取り出せるレコード
+pub trait Record {
+    /// フィールドの値を返す。存在しないフィールドの場合Noneを返す
+    /// # 引数
+    /// - name : フィールド名
+    fn field(&self, name: &str) -> Option<Cow<'_, Value>>;
+}
+
+impl Record for HashMap<String, Value> {
+    fn field(&self, name: &str) -> Option<Cow<'_, Value>> {
+        self.get(name).map(Cow::Borrowed)
+    }
+}
+
+/// ソートのキーとなるフィールドとそのソート順
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: String,
+    pub order: SortOrder,
+}
+
+/// 複数のフィールドによるソートの指定
+/// "age desc, last_name asc"のような文字列から作る。ソート順を省略した場合は昇順になる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortSpec {
+    pub keys: Vec<SortKey>,
+}
+
+impl FromStr for SortSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s
+            .split(',')
+            .map(|item| {
+                let mut words = item.split_whitespace();
+                let field = words
+                    .next()
+                    .ok_or_else(|| format!("Missing field name in sort spec. (spec: {:?})", s))?;
+                let order = match words.next() {
+                    None => Ascending,
+                    Some(word) if word.eq_ignore_ascii_case("asc") => Ascending,
+                    Some(word) if word.eq_ignore_ascii_case("desc") => Descending,
+                    Some(word) => {
+                        return Err(format!(
+                            "Unknown sort direction {:?} for field {:?}. (expected: asc or desc)",
+                            word, field
+                        ))
+                    }
+                };
+                if let Some(word) = words.next() {
+                    return Err(format!(
+                        "Unexpected {:?} after the sort direction of field {:?}.",
+                        word, field
+                    ));
+                }
+                Ok(SortKey {
+                    field: field.to_string(),
+                    order,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { keys })
+    }
+}
+
+impl SortSpec {
+    /// レコードを比較するためのクロージャを作る。クロージャはfourth::sort_byにそのまま渡せる
+    /// # 引数
+    /// - records : ソートするレコード。すべてのレコードに指定のフィールドがあるか検査する
+    /// # 戻り値
+    /// - 比較のためのクロージャ。存在しないフィールドがある場合Err
+    pub fn comparator<R: Record>(
+        &self,
+        records: &[R],
+    ) -> Result<impl Fn(&R, &R) -> Ordering + Sync, String> {
+        for (i, record) in records.iter().enumerate() {
+            for key in &self.keys {
+                if record.field(&key.field).is_none() {
+                    return Err(format!(
+                        "Unknown field {:?} in record. (index: {})",
+                        key.field, i
+                    ));
+                }
+            }
+        }
+
+        let keys = self.keys.clone();
+        Ok(move |a: &R, b: &R| {
+            for key in &keys {
+                let ordering = a.field(&key.field).cmp(&b.field(&key.field));
+                let ordering = match key.order {
+                    Ascending => ordering,
+                    Descending => ordering.reverse(),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            Ordering::Equal
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Record, SortKey, SortSpec, Value};
+    use crate::fourth;
+    use crate::SortOrder::*;
+    use std::borrow::Cow;
+    use std::cmp::Ordering::*;
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq)]
+    struct Student {
+        first_name: String,
+        last_name: String,
+        age: u8,
+    }
+
+    impl Student {
+        fn new(first_name: &str, last_name: &str, age: u8) -> Self {
+            Self {
+                first_name: first_name.to_string(),
+                last_name: last_name.to_string(),
+                age,
+            }
+        }
+    }
+
+    impl Record for &Student {
+        fn field(&self, name: &str) -> Option<Cow<'_, Value>> {
+            match name {
+                "first_name" => Some(Cow::Owned(Value::Str(self.first_name.clone()))),
+                "last_name" => Some(Cow::Owned(Value::Str(self.last_name.clone()))),
+                "age" => Some(Cow::Owned(Value::Int(self.age.into()))),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn parse_spec() {
+        let spec: SortSpec = "age desc, last_name ASC,first_name".parse().unwrap();
+
+        assert_eq!(
+            spec.keys,
+            vec![
+                SortKey {
+                    field: "age".to_string(),
+                    order: Descending
+                },
+                SortKey {
+                    field: "last_name".to_string(),
+                    order: Ascending
+                },
+                SortKey {
+                    field: "first_name".to_string(),
+                    order: Ascending
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_bad_direction() {
+        let err = "age down".parse::<SortSpec>().unwrap_err();
+
+        assert!(err.contains("\"down\""));
+        assert!(err.contains("\"age\""));
+    }
+
+    #[test]
+    fn parse_missing_field() {
+        assert!("age desc, ".parse::<SortSpec>().is_err());
+        assert!("age desc nulls".parse::<SortSpec>().is_err());
+    }
+
+    #[test]
+    fn sort_students_by_spec() {
+        let taro = Student::new("Taro", "Yamada", 16);
+        let hanako = Student::new("Hanako", "Yamada", 16);
+        let kyoko = Student::new("Kyoko", "Ito", 15);
+        let ryosuke = Student::new("Ryosuke", "Hayashi", 16);
+
+        let mut array = vec![&taro, &hanako, &kyoko, &ryosuke];
+
+        let expected = vec![&ryosuke, &hanako, &taro, &kyoko];
+
+        let spec: SortSpec = "age desc, last_name asc, first_name".parse().unwrap();
+        let comparator = spec.comparator(&array).unwrap();
+        assert!(fourth::sort_by(&mut array, &comparator).is_ok());
+
+        assert_eq!(array, expected);
+    }
+
+    // 存在しないフィールドを指定した場合
+    #[test]
+    fn unknown_field() {
+        let taro = Student::new("Taro", "Yamada", 16);
+        let array = vec![&taro];
+
+        let spec: SortSpec = "age desc, grade".parse().unwrap();
+        let err = spec.comparator(&array).err().unwrap();
+
+        assert!(err.contains("\"grade\""));
+    }
+
+    #[test]
+    fn compare_int_and_float() {
+        assert_eq!(Value::Int(10).cmp(&Value::Float(0.5)), Greater);
+        assert_eq!(Value::Float(0.5).cmp(&Value::Int(10)), Less);
+        assert_eq!(Value::Int(-1).cmp(&Value::Float(-0.5)), Less);
+        assert_eq!(Value::Int(1).cmp(&Value::Float(1.0)), Less);
+        assert_eq!(Value::Float(1.0).cmp(&Value::Int(1)), Greater);
+        // f64に変換すると丸められる整数
+        let big = (1i64 << 53) + 1;
+        assert_eq!(
+            Value::Int(big).cmp(&Value::Float((1u64 << 53) as f64)),
+            Greater
+        );
+        assert_eq!(Value::Int(i64::MAX).cmp(&Value::Float(f64::INFINITY)), Less);
+        assert_eq!(Value::Int(i64::MIN).cmp(&Value::Float(f64::MIN)), Greater);
+        assert_eq!(Value::Int(0).cmp(&Value::Float(f64::NAN)), Less);
+    }
+
+    // 整数と浮動小数点数が混ざった列
+    #[test]
+    fn sort_rows_with_mixed_numbers() {
+        let row = |name: &str, score: Value| {
+            let mut row = HashMap::new();
+            row.insert("name".to_string(), Value::Str(name.to_string()));
+            row.insert("score".to_string(), score);
+            row
+        };
+        let mut rows = vec![
+            row("a", Value::Int(10)),
+            row("b", Value::Float(0.5)),
+            row("c", Value::Float(10.0)),
+            row("d", Value::Int(-3)),
+            row("e", Value::Float(2.25)),
+            row("f", Value::Null),
+            row("g", Value::Int(2)),
+            row("h", Value::Float(-7.5)),
+        ];
+
+        let spec: SortSpec = "score".parse().unwrap();
+        let comparator = spec.comparator(&rows).unwrap();
+        assert!(fourth::sort_by(&mut rows, &comparator).is_ok());
+
+        let names: Vec<_> = rows.iter().map(|row| row["name"].clone()).collect();
+        let expected: Vec<_> = ["f", "h", "d", "b", "g", "e", "a", "c"]
+            .iter()
+            .map(|name| Value::Str(name.to_string()))
+            .collect();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn sort_rows_by_spec() {
+        let row = |name: &str, score: Value| {
+            let mut row = HashMap::new();
+            row.insert("name".to_string(), Value::Str(name.to_string()));
+            row.insert("score".to_string(), score);
+            row
+        };
+        let mut rows = vec![
+            row("a", Value::Int(3)),
+            row("b", Value::Null),
+            row("c", Value::Int(10)),
+            row("d", Value::Int(3)),
+        ];
+
+        let spec: SortSpec = "score desc, name desc".parse().unwrap();
+        let comparator = spec.comparator(&rows).unwrap();
+        assert!(fourth::sort_by(&mut rows, &comparator).is_ok());
+
+        let names: Vec<_> = rows.iter().map(|row| row["name"].clone()).collect();
+        assert_eq!(
+            names,
+            vec![
+                Value::Str("c".to_string()),
+                Value::Str("d".to_string()),
+                Value::Str("a".to_string()),
+                Value::Str("b".to_string()),
+            ]
+        );
+    }
+}
@@ -1,3 +1,7 @@
+use crate::checked::{Checker, Inconsistency};
+use crate::hybrid::{merge_small, sort_small, BaseCase};
+use crate::utils::apply_indices;
+use crate::SortError;
 use crate::SortOrder;
 use crate::SortOrder::*;
 use std::cmp::Ordering;
@@ -9,8 +13,8 @@ use std::cmp::Ordering::*;
 /// - order : ソート順
 pub fn sort<T: Ord>(array: &mut [T], order: &SortOrder) -> Result<(), String> {
     match *order {
-        Ascending => sort_by(array, &|a, b| a.cmp(&b)),
-        Descending => sort_by(array, &|a, b| b.cmp(&a)),
+        Ascending => sort_by(array, &|a, b| a.cmp(b)),
+        Descending => sort_by(array, &|a, b| b.cmp(a)),
     }
 }
 
@@ -21,6 +25,80 @@ pub fn sort<T: Ord>(array: &mut [T], order: &SortOrder) -> Result<(), String> {
 /// # 戻り値
 /// - 要素数が2^nの場合Okを、それ以外の場合Errを返す
 pub fn sort_by<T, F>(array: &mut [T], comparator: &F) -> Result<(), String>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    sort_hybrid_by(array, comparator, &BaseCase::Recursive)
+}
+
+/// キーを比較して配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// キーは比較のたびに計算される
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - key : 要素からキーを取り出すクロージャ。キーはOrdを実装しなければならない。
+/// - order : ソート順
+pub fn sort_by_key<T, K, F>(array: &mut [T], key: &F, order: &SortOrder) -> Result<(), String>
+where
+    K: Ord,
+    F: Fn(&T) -> K,
+{
+    match *order {
+        Ascending => sort_by(array, &|a, b| key(a).cmp(&key(b))),
+        Descending => sort_by(array, &|a, b| key(b).cmp(&key(a))),
+    }
+}
+
+/// キーを比較して配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// キーは各要素につき1回だけ計算し、キーと添字の組をソートしてから配列を並べ替える
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - key : 要素からキーを取り出すクロージャ。キーはOrdを実装しなければならない。
+/// - order : ソート順
+pub fn sort_by_cached_key<T, K, F>(
+    array: &mut [T],
+    key: &F,
+    order: &SortOrder,
+) -> Result<(), String>
+where
+    K: Ord,
+    F: Fn(&T) -> K,
+{
+    let mut keys: Vec<(K, usize)> = array.iter().map(key).zip(0..).collect();
+    sort(&mut keys, order)?;
+
+    let mut indices: Vec<usize> = keys.into_iter().map(|(_, i)| i).collect();
+    apply_indices(array, &mut indices);
+    Ok(())
+}
+
+/// 小さな部分配列を指定の方法でソートしながら、配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。また、要素はOrdを実装しなければならない。
+/// - order : ソート順
+/// - base_case : 小さな部分配列のソート方法
+pub fn sort_hybrid<T: Ord>(
+    array: &mut [T],
+    order: &SortOrder,
+    base_case: &BaseCase,
+) -> Result<(), String> {
+    match *order {
+        Ascending => sort_hybrid_by(array, &|a, b| a.cmp(b), base_case),
+        Descending => sort_hybrid_by(array, &|a, b| b.cmp(a), base_case),
+    }
+}
+
+/// 小さな部分配列を指定の方法でソートしながら、配列をソートする。配列の要素が2^nではない場合、エラーを返す
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ
+/// - base_case : 小さな部分配列のソート方法
+/// # 戻り値
+/// - 要素数が2^nの場合Okを、それ以外の場合Errを返す
+pub fn sort_hybrid_by<T, F>(
+    array: &mut [T],
+    comparator: &F,
+    base_case: &BaseCase,
+) -> Result<(), String>
 where
     F: Fn(&T, &T) -> Ordering,
 {
@@ -28,7 +106,7 @@ where
         0 => Ok(()),
         n => {
             if n.is_power_of_two() {
-                do_sort(array, comparator, true);
+                do_sort(array, comparator, true, base_case);
                 Ok(())
             } else {
                 Err(format!(
@@ -40,24 +118,77 @@ where
     }
 }
 
+/// 失敗する可能性のある比較で配列をソートする
+/// 比較がエラーを返した時点でソートを中断し、そのエラーを返す
+/// 中断した場合も、配列は入力の要素を並べ替えたものになっている
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ。比較できない場合はErrを返す
+/// # 戻り値
+/// - ソートできた場合Okを、要素数が2^nではない場合や比較がエラーを返した場合Errを返す
+pub fn try_sort_by<T, E, F>(array: &mut [T], comparator: &F) -> Result<(), SortError<E>>
+where
+    F: Fn(&T, &T) -> Result<Ordering, E>,
+{
+    match array.len() {
+        0 => Ok(()),
+        n => {
+            if n.is_power_of_two() {
+                try_do_sort(array, comparator, true).map_err(SortError::Comparator)
+            } else {
+                Err(SortError::NotPowerOfTwo(n))
+            }
+        }
+    }
+}
+
+/// 比較のためのクロージャを検査しながら配列をソートする。デバッグ用
+/// ソート中の比較を抜き取って反対称律と推移律を検査し、ソート後にソートされているかを検査する
+/// 矛盾が見つかった場合、配列はソート前のまま変更されない
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない。
+/// - comparator : 大小比較するためのクロージャ
+/// # 戻り値
+/// - ソートできた場合Okを、要素数が2^nではない場合や矛盾が見つかった場合Errを返す
+pub fn checked_sort_by<T, F>(
+    array: &mut [T],
+    comparator: &F,
+) -> Result<(), SortError<Inconsistency>>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    // 要素の代わりに添字をソートし、矛盾の原因となった要素をソート前の位置で示せるようにする
+    let mut indices: Vec<usize> = (0..array.len()).collect();
+    {
+        let checker = Checker::new(array, comparator);
+        try_sort_by(&mut indices, &|&a, &b| checker.compare(a, b))?;
+        checker
+            .verify_sorted(&indices)
+            .map_err(SortError::Comparator)?;
+    }
+    apply_indices(array, &mut indices);
+    Ok(())
+}
+
 /// 配列をソートする
 /// # 引数
 /// - array : 配列。ただし、要素数は2^nでなければならない
 /// - comparator : 比較のためのクロージャ
 /// - forward : 昇順の場合true, 降順の場合false
-fn do_sort<T, F>(array: &mut [T], comparator: &F, forward: bool)
+/// - base_case : 小さな部分配列のソート方法
+fn do_sort<T, F>(array: &mut [T], comparator: &F, forward: bool, base_case: &BaseCase)
 where
     F: Fn(&T, &T) -> Ordering,
 {
-    if array.len() <= 1 {
+    if array.len() <= 1 || sort_small(array, comparator, forward, base_case) {
         return;
     }
     // バイトニック列を作る
     let mid = array.len() / 2;
-    do_sort(&mut array[..mid], comparator, true); // 前半を昇順
-    do_sort(&mut array[mid..], comparator, false); // 後半を降順でソート
-    
-    sub_sort(array, comparator, forward);
+    do_sort(&mut array[..mid], comparator, true, base_case); // 前半を昇順
+    do_sort(&mut array[mid..], comparator, false, base_case); // 後半を降順でソート
+
+    sub_sort(array, comparator, forward, base_case);
 }
 
 /// バイトニック列をソートする
@@ -65,19 +196,20 @@ where
 /// - bitonic_array : バイトニック列
 /// - comparator : 比較のためのクロージャ
 /// - forward : 昇順の場合true, 降順の場合false
-fn sub_sort<T, F>(bitonic_array: &mut [T], comparator: &F, forward: bool)
+/// - base_case : 小さな部分配列のソート方法
+fn sub_sort<T, F>(bitonic_array: &mut [T], comparator: &F, forward: bool, base_case: &BaseCase)
 where
     F: Fn(&T, &T) -> Ordering,
 {
-    if bitonic_array.len() <= 1 {
+    if bitonic_array.len() <= 1 || merge_small(bitonic_array, comparator, forward, base_case) {
         return;
     }
     // 比較＆入れ替えによって並び順をascに近づける（ソートは不完全。半分にするとそれぞれがバイトニック列になる）
     compare_and_swap(bitonic_array, comparator, forward);
 
     let mid = bitonic_array.len() / 2;
-    sub_sort(&mut bitonic_array[..mid], comparator, forward);
-    sub_sort(&mut bitonic_array[mid..], comparator, forward);
+    sub_sort(&mut bitonic_array[..mid], comparator, forward, base_case);
+    sub_sort(&mut bitonic_array[mid..], comparator, forward, base_case);
 }
 
 /// 各要素を要素数n / 2だけ右の要素と比較し、昇順か降順かに応じて並べ替える
@@ -107,11 +239,76 @@ where
     }
 }
 
+/// 失敗する可能性のある比較で配列をソートする
+/// # 引数
+/// - array : 配列。ただし、要素数は2^nでなければならない
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+fn try_do_sort<T, E, F>(array: &mut [T], comparator: &F, forward: bool) -> Result<(), E>
+where
+    F: Fn(&T, &T) -> Result<Ordering, E>,
+{
+    if array.len() <= 1 {
+        return Ok(());
+    }
+    let mid = array.len() / 2;
+    try_do_sort(&mut array[..mid], comparator, true)?;
+    try_do_sort(&mut array[mid..], comparator, false)?;
+
+    try_sub_sort(array, comparator, forward)
+}
+
+/// 失敗する可能性のある比較でバイトニック列をソートする
+/// # 引数
+/// - bitonic_array : バイトニック列
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+fn try_sub_sort<T, E, F>(bitonic_array: &mut [T], comparator: &F, forward: bool) -> Result<(), E>
+where
+    F: Fn(&T, &T) -> Result<Ordering, E>,
+{
+    if bitonic_array.len() <= 1 {
+        return Ok(());
+    }
+    try_compare_and_swap(bitonic_array, comparator, forward)?;
+
+    let mid = bitonic_array.len() / 2;
+    try_sub_sort(&mut bitonic_array[..mid], comparator, forward)?;
+    try_sub_sort(&mut bitonic_array[mid..], comparator, forward)
+}
+
+/// 失敗する可能性のある比較で、各要素を要素数n / 2だけ右の要素と比較し並べ替える
+/// # 引数
+/// - array : 並び替え対象の配列
+/// - comparator : 比較のためのクロージャ
+/// - forward : 昇順の場合true, 降順の場合false
+fn try_compare_and_swap<T, E, F>(array: &mut [T], comparator: &F, forward: bool) -> Result<(), E>
+where
+    F: Fn(&T, &T) -> Result<Ordering, E>,
+{
+    let mid = array.len() / 2;
+    let expected = if forward { Greater } else { Less };
+
+    for i in 0..mid {
+        if comparator(&array[i], &array[i + mid])? == expected {
+            array.swap(i, i + mid);
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{sort, sort_by};
+    use super::{
+        checked_sort_by, sort, sort_by, sort_by_cached_key, sort_by_key, sort_hybrid, try_sort_by,
+    };
+    use crate::checked::Inconsistency;
+    use crate::hybrid::BaseCase;
     use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use crate::SortError;
     use crate::SortOrder::*;
+    use std::cell::Cell;
+    use std::cmp::Ordering::*;
 
     #[derive(Debug, PartialEq)] //これがないとassert_eq!ができない
     struct Student {
@@ -166,6 +363,71 @@ mod tests {
         assert_eq!(array, expected);
     }
 
+    #[test]
+    fn sort_students_by_key_age_descending() {
+        let taro = Student::new("Taro", "Yamada", 16);
+        let hanako = Student::new("Hanako", "Yamada", 14);
+        let kyoko = Student::new("Kyoko", "Ito", 15);
+        let ryosuke = Student::new("Ryosuke", "Hayashi", 17);
+
+        let mut array = vec![&taro, &hanako, &kyoko, &ryosuke];
+
+        let expected = vec![&ryosuke, &taro, &kyoko, &hanako];
+
+        assert!(sort_by_key(&mut array, &|s| s.age, &Descending).is_ok());
+
+        assert_eq!(array, expected);
+    }
+
+    #[test]
+    fn sort_students_by_cached_key_name_ascending() {
+        let taro = Student::new("Taro", "Yamada", 16);
+        let hanako = Student::new("Hanako", "Yamada", 14);
+        let kyoko = Student::new("Kyoko", "Ito", 15);
+        let ryosuke = Student::new("Ryosuke", "Hayashi", 17);
+
+        let mut array = vec![&taro, &hanako, &kyoko, &ryosuke];
+
+        let expected = vec![&ryosuke, &kyoko, &hanako, &taro];
+
+        assert!(sort_by_cached_key(
+            &mut array,
+            &|s| format!("{} {}", s.last_name, s.first_name),
+            &Ascending
+        )
+        .is_ok());
+
+        assert_eq!(array, expected);
+    }
+
+    // キーは各要素につき1回だけ計算される
+    #[test]
+    fn sort_by_cached_key_computes_each_key_once() {
+        let mut x = new_u32_vec(1 << 10);
+        let calls = Cell::new(0);
+
+        let key = |v: &u32| {
+            calls.set(calls.get() + 1);
+            v.count_ones()
+        };
+        assert_eq!(sort_by_cached_key(&mut x, &key, &Ascending), Ok(()));
+
+        assert_eq!(calls.get(), 1 << 10);
+        assert!(x
+            .windows(2)
+            .all(|pair| pair[0].count_ones() <= pair[1].count_ones()));
+    }
+
+    // 要素が2のべき乗個ではない場合
+    #[test]
+    fn sort_by_cached_key_not_power_of_two() {
+        let mut array: Vec<u32> = vec![45, 21, 11];
+
+        assert!(sort_by_cached_key(&mut array, &|v| *v, &Ascending).is_err());
+
+        assert_eq!(array, vec![45, 21, 11]);
+    }
+
     // 昇順ソートのテスト
     #[test]
     fn sort_u32_ascending() {
@@ -311,4 +573,150 @@ mod tests {
         assert_eq!(sort(&mut x, &Descending), Ok(()));
         assert!(is_sorted_descending(&x));
     }
+
+    #[test]
+    fn sort_u32_large_hybrid() {
+        for base_case in &[BaseCase::Network, BaseCase::Insertion] {
+            let mut x = new_u32_vec(2 << 16);
+            let mut y = new_u32_vec(2 << 16);
+
+            assert_eq!(sort_hybrid(&mut x, &Ascending, base_case), Ok(()));
+            assert_eq!(sort_hybrid(&mut y, &Descending, base_case), Ok(()));
+
+            assert!(is_sorted_ascending(&x));
+            assert!(is_sorted_descending(&y));
+        }
+    }
+
+    // 基底ケースより小さい配列
+    #[test]
+    fn sort_u32_hybrid_smaller_than_cutoff() {
+        let mut array: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+
+        assert!(sort_hybrid(&mut array, &Descending, &BaseCase::Network).is_ok());
+
+        assert_eq!(array, vec![330, 110, 30, 21, 20, 11, 10, 4]);
+    }
+
+    #[test]
+    fn try_sort_u32_ok() {
+        let mut x = new_u32_vec(1 << 10);
+
+        assert_eq!(
+            try_sort_by(&mut x, &|a: &u32, b: &u32| Ok::<_, ()>(b.cmp(a))),
+            Ok(())
+        );
+        assert!(is_sorted_descending(&x));
+    }
+
+    // 比較がエラーを返した場合、中断してエラーを返す。配列は入力を並べ替えたものになっている
+    #[test]
+    fn try_sort_u32_comparator_error() {
+        let input = new_u32_vec(1 << 10);
+        let mut x = input.clone();
+        let calls = Cell::new(0);
+        let comparator = |a: &u32, b: &u32| {
+            calls.set(calls.get() + 1);
+            if calls.get() == 500 {
+                Err("failed")
+            } else {
+                Ok(a.cmp(b))
+            }
+        };
+
+        assert_eq!(
+            try_sort_by(&mut x, &comparator),
+            Err(SortError::Comparator("failed"))
+        );
+
+        let (mut actual, mut expected) = (x, input);
+        actual.sort();
+        expected.sort();
+        assert_eq!(actual, expected);
+    }
+
+    // 要素が2のべき乗個ではない場合
+    #[test]
+    fn try_sort_not_power_of_two() {
+        let mut array: Vec<u32> = vec![45, 21, 11];
+
+        assert_eq!(
+            try_sort_by(&mut array, &|a: &u32, b: &u32| Ok::<_, ()>(a.cmp(b))),
+            Err(SortError::NotPowerOfTwo(3))
+        );
+    }
+
+    #[test]
+    fn checked_sort_u32_ok() {
+        let mut x = new_u32_vec(1 << 10);
+
+        assert_eq!(
+            checked_sort_by(&mut x, &|a: &u32, b: &u32| a.cmp(b)),
+            Ok(())
+        );
+        assert!(is_sorted_ascending(&x));
+    }
+
+    // 反対称律を満たさない比較の場合
+    #[test]
+    fn checked_sort_not_antisymmetric() {
+        let input = new_u32_vec(1 << 10);
+        let mut x = input.clone();
+
+        let result = checked_sort_by(&mut x, &|a: &u32, b: &u32| {
+            if a.cmp(b) == Equal {
+                Equal
+            } else {
+                Less
+            }
+        });
+
+        assert!(matches!(
+            result,
+            Err(SortError::Comparator(Inconsistency::Antisymmetry { .. }))
+        ));
+        assert_eq!(x, input);
+    }
+
+    // 推移律を満たさない比較の場合
+    #[test]
+    fn checked_sort_not_transitive() {
+        let mut x = new_u32_vec(1 << 10);
+
+        // 値の差が小さいものを等しいとみなす
+        let result = checked_sort_by(&mut x, &|a: &u32, b: &u32| {
+            if (*a as i64 - *b as i64).abs() < 1 << 30 {
+                Equal
+            } else {
+                a.cmp(b)
+            }
+        });
+
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("not transitive") || message.contains("not sorted"));
+    }
+
+    // じゃんけんの比較（0 < 1 < 2 < 0）では、3つの異なる値を検査すると必ず推移律の違反になる
+    // 要素数が少なく逐次にソートされるので、比較の順序は毎回同じになる
+    #[test]
+    fn checked_sort_rock_paper_scissors() {
+        let input: Vec<u32> = (0..1 << 6).map(|i| i % 3).collect();
+        let mut x = input.clone();
+
+        let result = checked_sort_by(&mut x, &|a: &u32, b: &u32| {
+            if a == b {
+                Equal
+            } else if (a + 1) % 3 == *b {
+                Less
+            } else {
+                Greater
+            }
+        });
+
+        assert!(matches!(
+            result,
+            Err(SortError::Comparator(Inconsistency::Transitivity { .. }))
+        ));
+        assert_eq!(x, input);
+    }
 }
@@ -23,3 +23,58 @@ pub fn is_sorted_ascending<T: Ord>(x: &[T]) -> bool {
 pub fn is_sorted_descending<T: Ord>(x: &[T]) -> bool {
     x.windows(2).all(|pair| pair[0] >= pair[1])
 }
+
+/// apply_indicesで使える添字の型
+pub(crate) trait PermutationIndex: Copy {
+    fn to_usize(self) -> usize;
+}
+
+impl PermutationIndex for usize {
+    fn to_usize(self) -> usize {
+        self
+    }
+}
+
+impl PermutationIndex for u32 {
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+}
+
+/// indices[i]番目の要素がi番目に来るように配列を並べ替える
+/// # 引数
+/// - array : 並び替え対象の配列
+/// - indices : 並び替え後の各位置に来る要素の元の添字。処理の途中で書き換えられる
+pub(crate) fn apply_indices<T, I: PermutationIndex>(array: &mut [T], indices: &mut [I]) {
+    apply_indices_with(indices, |i, j| array.swap(i, j));
+}
+
+/// indices[i]番目の要素がi番目に来るように、入れ替えのクロージャを使って並べ替える
+/// スライスの要素ではないもの（バイト列に並んだレコードなど）を並べ替えるときに使う
+/// # 引数
+/// - indices : 並び替え後の各位置に来る要素の元の添字。処理の途中で書き換えられる
+/// - swap : i番目とj番目の要素を入れ替えるクロージャ
+pub(crate) fn apply_indices_with<I, S>(indices: &mut [I], mut swap: S)
+where
+    I: PermutationIndex,
+    S: FnMut(usize, usize),
+{
+    for i in 0..indices.len() {
+        // 既に入れ替え済みの位置を指している場合、入れ替え先をたどる
+        let mut index = indices[i];
+        while index.to_usize() < i {
+            index = indices[index.to_usize()];
+        }
+        indices[i] = index;
+        swap(i, index.to_usize());
+    }
+}
+
+/// バイト列の先頭8バイトをビッグエンディアンの整数にする。8バイトに満たない場合は0で埋める
+/// 整数の大小はバイト列の先頭8バイトの辞書順と一致する
+pub(crate) fn byte_prefix(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    let len = bytes.len().min(8);
+    buf[..len].copy_from_slice(&bytes[..len]);
+    u64::from_be_bytes(buf)
+}